@@ -0,0 +1,246 @@
+/*
+ * ANONYMOUS EDITORS
+ *
+ * Recognizes edits by IP addresses and temporary accounts, looks up
+ * the country of IP addresses and holds back floods of edits from
+ * one address range.
+ */
+
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    sync::Mutex,
+};
+
+use crate::config::AnonConfig;
+
+pub enum Editor {
+    Registered,
+    Ip(IpAddr),
+    Temporary,
+}
+
+// What to do with an IP edit, as far as flooding is concerned
+pub enum FloodDecision {
+    Post,
+    // the range calmed down, post as usual after noting the edits held back
+    Resumed { range: String, held: usize },
+    // the range just started flooding, post an alert instead
+    Alert { range: String, edits: usize },
+    Hold,
+}
+
+#[derive(Default)]
+struct RangeActivity {
+    timestamps: VecDeque<i64>,
+    held: usize,
+}
+
+pub struct AnonEditors {
+    config: AnonConfig,
+    geoip: Option<maxminddb::Reader<Vec<u8>>>,
+    ranges: Mutex<HashMap<String, RangeActivity>>,
+}
+
+pub fn classify(user: &str, temp_account_prefix: &str) -> Editor {
+    if let Ok(ip) = user.parse::<IpAddr>() {
+        return Editor::Ip(ip);
+    }
+
+    match !temp_account_prefix.is_empty() && user.starts_with(temp_account_prefix) {
+        true => Editor::Temporary,
+        false => Editor::Registered,
+    }
+}
+
+// "203.0.113.7" -> "203.0.113.0/24"
+fn ip_range(ip: IpAddr, ipv4_prefix: u8, ipv6_prefix: u8) -> String {
+    match ip {
+        IpAddr::V4(ip) => {
+            let prefix = u32::from(ipv4_prefix.min(32));
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+
+            format!("{}/{}", std::net::Ipv4Addr::from(u32::from(ip) & mask), prefix)
+        }
+        IpAddr::V6(ip) => {
+            let prefix = u32::from(ipv6_prefix.min(128));
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+
+            format!("{}/{}", std::net::Ipv6Addr::from(u128::from(ip) & mask), prefix)
+        }
+    }
+}
+
+impl AnonEditors {
+    pub fn new(config: &AnonConfig) -> AnonEditors {
+        let geoip = config.geoip_database.as_ref().and_then(|path| {
+            match maxminddb::Reader::open_readfile(path) {
+                Ok(reader) => Some(reader),
+                Err(err) => {
+                    println!("couldn't open GeoIP database {}: {}", path, err);
+
+                    None
+                }
+            }
+        });
+
+        AnonEditors {
+            config: config.clone(),
+            geoip,
+            ranges: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn classify(&self, user: &str) -> Editor {
+        classify(user, &self.config.temp_account_prefix)
+    }
+
+    // ISO code of the country an address is located in
+    pub fn country(&self, ip: IpAddr) -> Option<String> {
+        let country: maxminddb::geoip2::Country = self.geoip.as_ref()?.lookup(ip).ok()?;
+
+        country.country?.iso_code.map(|iso_code| iso_code.to_string())
+    }
+
+    // Account for an edit by an IP address at the given time
+    pub fn note_edit(&self, ip: IpAddr, timestamp: i64) -> FloodDecision {
+        if self.config.flood_max_edits == 0 {
+            return FloodDecision::Post;
+        }
+
+        let range = ip_range(ip, self.config.flood_ipv4_prefix, self.config.flood_ipv6_prefix);
+
+        let window = self.config.flood_window_secs as i64;
+
+        let mut ranges = self.ranges.lock().unwrap();
+
+        let activity = ranges.entry(range.clone()).or_default();
+
+        activity.timestamps.push_back(timestamp);
+
+        while activity.timestamps.front().map_or(false, |first| timestamp - first >= window) {
+            activity.timestamps.pop_front();
+        }
+
+        let edits = activity.timestamps.len();
+
+        if edits > self.config.flood_max_edits {
+            activity.held += 1;
+
+            return match activity.held {
+                1 => FloodDecision::Alert { range, edits },
+                _ => FloodDecision::Hold,
+            };
+        }
+
+        match std::mem::replace(&mut activity.held, 0) {
+            0 => FloodDecision::Post,
+            held => FloodDecision::Resumed { range, held },
+        }
+    }
+
+    // Forget ranges that have been quiet for a whole window; returns
+    // those that had edits held back, with their number
+    pub fn take_calmed(&self, now: i64) -> Vec<(String, usize)> {
+        let window = self.config.flood_window_secs as i64;
+
+        let mut ranges = self.ranges.lock().unwrap();
+
+        let calmed = ranges
+            .iter()
+            .filter(|(_, activity)| activity.timestamps.back().map_or(true, |last| now - last >= window))
+            .map(|(range, activity)| (range.clone(), activity.held))
+            .collect::<Vec<(String, usize)>>();
+
+        for (range, _) in &calmed {
+            ranges.remove(range);
+        }
+
+        calmed.into_iter().filter(|(_, held)| *held > 0).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anon_editors(flood_max_edits: usize) -> AnonEditors {
+        AnonEditors::new(&AnonConfig {
+            flood_max_edits,
+            flood_window_secs: 60,
+            ..AnonConfig::default()
+        })
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn classifies_editors() {
+        assert!(matches!(classify("203.0.113.7", "~"), Editor::Ip(_)));
+        assert!(matches!(classify("2001:db8::1", "~"), Editor::Ip(_)));
+        assert!(matches!(classify("~2024-123", "~"), Editor::Temporary));
+        assert!(matches!(classify("~2024-123", ""), Editor::Registered));
+        assert!(matches!(classify("Alice", "~"), Editor::Registered));
+    }
+
+    #[test]
+    fn groups_addresses_by_range() {
+        assert_eq!(ip_range(ip("203.0.113.7"), 24, 64), "203.0.113.0/24");
+        assert_eq!(ip_range(ip("203.0.113.7"), 0, 64), "0.0.0.0/0");
+        assert_eq!(ip_range(ip("2001:db8:1:2:3::1"), 24, 64), "2001:db8:1:2::/64");
+    }
+
+    #[test]
+    fn is_off_by_default() {
+        let anon_editors = AnonEditors::new(&AnonConfig::default());
+
+        for _ in 0..100 {
+            assert!(matches!(anon_editors.note_edit(ip("203.0.113.7"), 1000), FloodDecision::Post));
+        }
+    }
+
+    #[test]
+    fn holds_back_floods() {
+        let anon_editors = anon_editors(2);
+
+        assert!(matches!(anon_editors.note_edit(ip("203.0.113.7"), 1000), FloodDecision::Post));
+        assert!(matches!(anon_editors.note_edit(ip("203.0.113.8"), 1001), FloodDecision::Post));
+        assert!(matches!(
+            anon_editors.note_edit(ip("203.0.113.9"), 1002),
+            FloodDecision::Alert { edits: 3, .. }
+        ));
+        assert!(matches!(anon_editors.note_edit(ip("203.0.113.7"), 1003), FloodDecision::Hold));
+
+        // other ranges are unaffected
+        assert!(matches!(anon_editors.note_edit(ip("198.51.100.1"), 1003), FloodDecision::Post));
+
+        // the range slowed down, but kept editing
+        match anon_editors.note_edit(ip("203.0.113.7"), 1062) {
+            FloodDecision::Resumed { range, held } => {
+                assert_eq!(range, "203.0.113.0/24");
+                assert_eq!(held, 2);
+            }
+            _ => panic!("expected the range to resume"),
+        }
+    }
+
+    #[test]
+    fn reports_ranges_gone_quiet() {
+        let anon_editors = anon_editors(1);
+
+        for timestamp in 1000..1004 {
+            anon_editors.note_edit(ip("203.0.113.7"), timestamp);
+        }
+
+        anon_editors.note_edit(ip("198.51.100.1"), 1000);
+
+        assert!(anon_editors.take_calmed(1030).is_empty());
+        assert_eq!(anon_editors.take_calmed(1063), vec![("203.0.113.0/24".to_string(), 3)]);
+
+        // forgotten once reported
+        assert!(anon_editors.take_calmed(1100).is_empty());
+        assert!(matches!(anon_editors.note_edit(ip("203.0.113.7"), 1100), FloodDecision::Post));
+    }
+}
@@ -0,0 +1,373 @@
+/*
+ * APPROVED REVS TRACKING
+ *
+ * Follows edits and approvals to know which pages have edits awaiting
+ * approval since their last approved revision. The state is written
+ * to disk on every change so it survives restarts.
+ */
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    sync::Mutex,
+};
+
+use crate::config::{ApprovalsConfig, WikiConfig};
+use crate::control::{format_duration, now_secs};
+use crate::{htmlescape_str, save_state};
+use crate::wiki_api::encode_query;
+
+#[derive(Serialize, Deserialize)]
+struct PendingPage {
+    // position of the wiki in the configuration
+    wiki: usize,
+    title: String,
+    // last approved revision, as far as known
+    approved_rev: Option<u32>,
+    latest_rev: u32,
+    // timestamp of the oldest unapproved edit
+    since: i64,
+    // timestamps of the unapproved edits seen, by revision
+    #[serde(default)]
+    edits: BTreeMap<u32, i64>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ApprovalsState {
+    // keyed by "<wiki>:<title>"
+    pending: BTreeMap<String, PendingPage>,
+    // day since the epoch the last report was posted on
+    last_report_day: u64,
+}
+
+pub struct ApprovalTracker {
+    config: ApprovalsConfig,
+    state: Mutex<ApprovalsState>,
+}
+
+fn page_key(wiki: usize, title: &str) -> String {
+    format!("{}:{}", wiki, title)
+}
+
+impl ApprovalTracker {
+    pub fn new(config: &ApprovalsConfig) -> ApprovalTracker {
+        let state = match fs::read_to_string(&config.state_path) {
+            Ok(raw) if config.enabled => serde_json::from_str(&raw).unwrap_or_else(|err| {
+                println!("couldn't parse approvals state {}: {}", config.state_path, err);
+
+                ApprovalsState::default()
+            }),
+            _ => ApprovalsState::default(),
+        };
+
+        ApprovalTracker {
+            config: config.clone(),
+            state: Mutex::new(state),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    pub fn is_tracked_namespace(&self, namespace: i64) -> bool {
+        self.config.enabled && self.config.namespaces.contains(&namespace)
+    }
+
+    fn save(&self, state: &ApprovalsState) {
+        if let Err(err) = save_state(&self.config.state_path, state) {
+            println!("couldn't save approvals state {}: {}", self.config.state_path, err);
+        }
+    }
+
+    // An edit of a page, from revision `old_rev` to `new_rev`
+    pub fn note_edit(&self, wiki: usize, title: &str, old_rev: Option<u32>, new_rev: u32, timestamp: i64) {
+        let mut state = self.state.lock().unwrap();
+
+        let page = state.pending
+            .entry(page_key(wiki, title))
+            .or_insert_with(|| PendingPage {
+                wiki,
+                title: title.to_string(),
+                // the page was fully approved before this edit
+                approved_rev: old_rev,
+                latest_rev: new_rev,
+                since: timestamp,
+                edits: BTreeMap::new(),
+            });
+
+        page.latest_rev = page.latest_rev.max(new_rev);
+        page.edits.insert(new_rev, timestamp);
+
+        self.save(&state);
+    }
+
+    pub fn note_approval(&self, wiki: usize, title: &str, rev_id: Option<u32>) {
+        let mut state = self.state.lock().unwrap();
+
+        let key = page_key(wiki, title);
+
+        let is_up_to_date = match (state.pending.get_mut(&key), rev_id) {
+            (None, _) => return,
+            (Some(page), Some(rev_id)) if rev_id < page.latest_rev => {
+                page.approved_rev = Some(rev_id);
+                page.edits.retain(|edit_rev_id, _| *edit_rev_id > rev_id);
+
+                // the edits up to the approved revision no longer await approval
+                if let Some(oldest) = page.edits.values().min() {
+                    page.since = *oldest;
+                }
+
+                false
+            }
+            // without a revision id, assume the latest one was approved
+            (Some(_), _) => true,
+        };
+
+        if is_up_to_date {
+            state.pending.remove(&key);
+        }
+
+        self.save(&state);
+    }
+
+    // The approval of a page was revoked, so all of it awaits approval.
+    // Pages not pending yet are only added if the formerly approved
+    // revision is known, the latest one they have as far as known.
+    pub fn note_unapproval(&self, wiki: usize, title: &str, formerly_approved_rev: Option<u32>, timestamp: i64) {
+        let mut state = self.state.lock().unwrap();
+
+        let key = page_key(wiki, title);
+
+        if let (false, Some(formerly_approved_rev)) = (state.pending.contains_key(&key), formerly_approved_rev) {
+            state.pending.insert(key.clone(), PendingPage {
+                wiki,
+                title: title.to_string(),
+                approved_rev: None,
+                latest_rev: formerly_approved_rev,
+                since: timestamp,
+                edits: BTreeMap::new(),
+            });
+        }
+
+        let page = match state.pending.get_mut(&key) {
+            Some(page) => page,
+            None => return,
+        };
+
+        page.approved_rev = None;
+        page.since = page.since.min(timestamp);
+
+        self.save(&state);
+    }
+
+    // A page was deleted, nothing of it awaits approval anymore
+    pub fn note_deletion(&self, wiki: usize, title: &str) {
+        let mut state = self.state.lock().unwrap();
+
+        if state.pending.remove(&page_key(wiki, title)).is_some() {
+            self.save(&state);
+        }
+    }
+
+    // A page was moved, its edits await approval under the new title
+    pub fn note_move(&self, wiki: usize, title: &str, target: &str) {
+        let mut state = self.state.lock().unwrap();
+
+        let mut page = match state.pending.remove(&page_key(wiki, title)) {
+            Some(page) => page,
+            None => return,
+        };
+
+        page.title = target.to_string();
+
+        state.pending.insert(page_key(wiki, target), page);
+
+        self.save(&state);
+    }
+
+    // "3 pages awaiting approval, oldest 4d 2h", followed by the pages
+    // with the diff since their last approved revision
+    pub fn render(&self, wikis: &[WikiConfig]) -> String {
+        let state = self.state.lock().unwrap();
+
+        if state.pending.is_empty() {
+            return "No pages awaiting approval.".to_string();
+        }
+
+        let now = now_secs() as i64;
+
+        let mut pages = state.pending.values().collect::<Vec<&PendingPage>>();
+
+        pages.sort_by_key(|page| page.since);
+
+        let lines = pages
+            .iter()
+            .take(self.config.report_limit)
+            .filter_map(|page| {
+                let wiki = wikis.get(page.wiki)?;
+
+                let latest_rev = page.latest_rev.to_string();
+
+                let url = match page.approved_rev {
+                    Some(approved_rev) => format!(
+                        "{}?{}",
+                        wiki.index_url,
+                        encode_query(&[
+                            ("title", &page.title),
+                            ("diff", &latest_rev),
+                            ("oldid", &approved_rev.to_string()),
+                        ])
+                    ),
+                    None => format!(
+                        "{}?{}",
+                        wiki.index_url,
+                        encode_query(&[("title", &page.title), ("action", "history")])
+                    ),
+                };
+
+                Some(format!(
+                    r#"• <a href="{}">{}</a>{} ({})"#,
+                    url,
+                    htmlescape_str(page.title.as_str()),
+                    match wiki.label.is_empty() {
+                        true => String::new(),
+                        false => format!(" [{}]", htmlescape_str(wiki.label.as_str())),
+                    },
+                    format_duration((now - page.since).max(0) as u64)
+                ))
+            })
+            .collect::<Vec<String>>();
+
+        format!(
+            "<b>{}</b> pages awaiting approval, oldest {}\n{}{}",
+            pages.len(),
+            format_duration((now - pages[0].since).max(0) as u64),
+            lines.join("\n"),
+            match pages.len() > lines.len() {
+                true => format!("\n… and {} more", pages.len() - lines.len()),
+                false => String::new(),
+            }
+        )
+    }
+
+    // The daily report once its hour has come, None otherwise or if
+    // nothing awaits approval
+    pub fn take_due_report(&self, wikis: &[WikiConfig]) -> Option<String> {
+        let now = now_secs();
+
+        let today = now / 86400;
+        let hour = (now % 86400 / 3600) as u8;
+
+        {
+            let mut state = self.state.lock().unwrap();
+
+            if !self.config.enabled || hour < self.config.report_hour || state.last_report_day >= today {
+                return None;
+            }
+
+            state.last_report_day = today;
+
+            self.save(&state);
+
+            if state.pending.is_empty() {
+                return None;
+            }
+        }
+
+        Some(self.render(wikis))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(name: &str) -> ApprovalTracker {
+        let state_path = std::env::temp_dir().join(format!("eop-approvals-test-{}-{}.json", name, std::process::id()));
+
+        let _ = fs::remove_file(&state_path);
+
+        ApprovalTracker::new(&ApprovalsConfig {
+            enabled: true,
+            state_path: state_path.to_string_lossy().into_owned(),
+            ..ApprovalsConfig::default()
+        })
+    }
+
+    // (title, approved revision, latest revision, since) of the pending pages
+    fn pending(tracker: &ApprovalTracker) -> Vec<(String, Option<u32>, u32, i64)> {
+        tracker.state.lock().unwrap()
+            .pending
+            .values()
+            .map(|page| (page.title.clone(), page.approved_rev, page.latest_rev, page.since))
+            .collect()
+    }
+
+    #[test]
+    fn tracks_edits_until_approved() {
+        let tracker = tracker("approved");
+
+        tracker.note_edit(0, "Page", Some(10), 11, 1000);
+        tracker.note_edit(0, "Page", Some(11), 12, 2000);
+        tracker.note_edit(1, "Page", None, 5, 1500);
+
+        assert_eq!(pending(&tracker), vec![
+            ("Page".to_string(), Some(10), 12, 1000),
+            ("Page".to_string(), None, 5, 1500),
+        ]);
+
+        tracker.note_approval(0, "Page", Some(12));
+        tracker.note_approval(1, "Page", None);
+
+        assert!(pending(&tracker).is_empty());
+    }
+
+    #[test]
+    fn partial_approval_moves_since() {
+        let tracker = tracker("partial");
+
+        tracker.note_edit(0, "Page", Some(10), 11, 1000);
+        tracker.note_edit(0, "Page", Some(11), 12, 2000);
+        tracker.note_edit(0, "Page", Some(12), 13, 3000);
+
+        tracker.note_approval(0, "Page", Some(11));
+
+        assert_eq!(pending(&tracker), vec![("Page".to_string(), Some(11), 13, 2000)]);
+    }
+
+    #[test]
+    fn unapproval_needs_a_revision() {
+        let tracker = tracker("unapproval");
+
+        tracker.note_unapproval(0, "Unknown", None, 1000);
+
+        assert!(pending(&tracker).is_empty());
+
+        tracker.note_unapproval(0, "Page", Some(20), 1000);
+        tracker.note_edit(0, "Other", Some(30), 31, 500);
+        tracker.note_unapproval(0, "Other", Some(30), 1000);
+
+        assert_eq!(pending(&tracker), vec![
+            ("Other".to_string(), None, 31, 500),
+            ("Page".to_string(), None, 20, 1000),
+        ]);
+    }
+
+    #[test]
+    fn follows_deletions_and_moves() {
+        let tracker = tracker("moves");
+
+        tracker.note_edit(0, "Deleted", Some(10), 11, 1000);
+        tracker.note_edit(0, "Moved", Some(20), 21, 1000);
+
+        tracker.note_deletion(0, "Deleted");
+        tracker.note_move(0, "Moved", "New title");
+
+        assert_eq!(pending(&tracker), vec![("New title".to_string(), Some(20), 21, 1000)]);
+
+        tracker.note_approval(0, "New title", Some(21));
+
+        assert!(pending(&tracker).is_empty());
+    }
+}
@@ -0,0 +1,755 @@
+/*
+ * TELEGRAM UPDATES
+ *
+ * Long-polls the bot's updates, reacts to inline keyboard presses
+ * and answers commands of the channel's admins.
+ */
+
+use std::{
+    collections::HashMap,
+    sync::Arc,
+};
+
+use telegram_bot::prelude::*;
+use telegram_bot::types::requests::{
+    Error, HttpRequest, JsonIdResponse, JsonRequestType, Request, RequestType, RequestUrl,
+};
+use telegram_bot::{
+    CanReplySendMessage, ChannelPost, InlineKeyboardButton, InlineKeyboardMarkup, Message, MessageChat,
+    MessageKind, Update, UpdateKind, User,
+};
+
+use crate::config::{ActionAccount, Config, Watcher, WikiConfig};
+use crate::control::{format_duration, now_secs, parse_duration, Control, Source};
+use crate::wiki_api::{WikiAction, WikiApiClient, WikiSession};
+use crate::{htmlescape_str, ConfiguredApi, GITHUB_ENDPOINT, JIRA_ENDPOINT, MEDIAWIKI_ENDPOINT, PAYPAL_ENDPOINT};
+
+// seconds a single getUpdates call is held open
+const POLL_TIMEOUT: i64 = 30;
+
+// seconds the list of channel admins is cached
+const ADMINS_TTL: u64 = 600;
+
+const DEFAULT_MUTE_SECS: u64 = 3600;
+
+fn source_endpoint(source: Source) -> &'static str {
+    match source {
+        Source::MediaWiki => MEDIAWIKI_ENDPOINT,
+        Source::GitHub => GITHUB_ENDPOINT,
+        Source::Jira => JIRA_ENDPOINT,
+        Source::PayPal => PAYPAL_ENDPOINT,
+    }
+}
+
+// "all" or a single source name
+fn parse_sources(arg: Option<&str>) -> Option<Vec<Source>> {
+    match arg {
+        None | Some("all") => Some(Source::ALL.to_vec()),
+        Some(name) => Source::from_name(name).map(|source| vec![source]),
+    }
+}
+
+fn action_code(action: WikiAction) -> char {
+    match action {
+        WikiAction::Patrol => 'p',
+        WikiAction::Rollback => 'r',
+        WikiAction::Approve => 'a',
+    }
+}
+
+fn action_from_code(code: char) -> Option<WikiAction> {
+    match code {
+        'p' => Some(WikiAction::Patrol),
+        'r' => Some(WikiAction::Rollback),
+        'a' => Some(WikiAction::Approve),
+        _ => None,
+    }
+}
+
+fn action_label(action: WikiAction) -> &'static str {
+    match action {
+        WikiAction::Patrol => "Mark patrolled",
+        WikiAction::Rollback => "Rollback",
+        WikiAction::Approve => "Approve revision",
+    }
+}
+
+fn action_past_tense(action: WikiAction) -> &'static str {
+    match action {
+        WikiAction::Patrol => "patrolled",
+        WikiAction::Rollback => "rolled back",
+        WikiAction::Approve => "approved",
+    }
+}
+
+// Keyboard of a revision message with the actions still available.
+// The callback data is "<action>:<rev id>:<available actions>:<wiki>",
+// e.g. "p:1234:pra:0", where the wiki is its position in the configuration.
+pub fn action_keyboard(wiki_idx: usize, rev_id: u32, available: &[WikiAction]) -> InlineKeyboardMarkup {
+    let mut keyboard = InlineKeyboardMarkup::new();
+
+    let codes = available.iter().cloned().map(action_code).collect::<String>();
+
+    let buttons = available
+        .iter()
+        .map(|action| InlineKeyboardButton::callback(
+            action_label(*action),
+            format!("{}:{}:{}:{}", action_code(*action), rev_id, codes, wiki_idx),
+        ))
+        .collect::<Vec<InlineKeyboardButton>>();
+
+    if !buttons.is_empty() {
+        keyboard.add_row(buttons);
+    }
+
+    keyboard
+}
+
+fn parse_callback_data(data: &str) -> Option<(WikiAction, usize, u32, Vec<WikiAction>)> {
+    let mut parts = data.splitn(4, ':');
+
+    let action = action_from_code(parts.next()?.chars().next()?)?;
+    let rev_id = parts.next()?.parse::<u32>().ok()?;
+    let available = parts.next()?.chars().filter_map(action_from_code).collect();
+
+    // buttons posted before multiple wikis were supported lack the wiki
+    let wiki_idx = match parts.next() {
+        Some(wiki_idx) => wiki_idx.parse::<usize>().ok()?,
+        None => 0,
+    };
+
+    Some((action, wiki_idx, rev_id, available))
+}
+
+// getUpdates, leaving the updates undecoded. telegram-bot fails to
+// decode some updates, e.g. button presses on channel posts, which
+// would fail the whole batch and stall polling for good.
+#[derive(Serialize)]
+struct GetRawUpdates {
+    offset: i64,
+    timeout: i64,
+}
+
+impl Request for GetRawUpdates {
+    type Type = JsonRequestType<Self>;
+    type Response = JsonIdResponse<Vec<serde_json::Value>>;
+
+    fn serialize(&self) -> Result<HttpRequest, Error> {
+        Self::Type::serialize(RequestUrl::method("getUpdates"), self)
+    }
+}
+
+// answerCallbackQuery for a query known only by its id
+#[derive(Serialize)]
+struct AnswerButtonPress<'s> {
+    callback_query_id: &'s str,
+    text: &'s str,
+}
+
+impl<'s> Request for AnswerButtonPress<'s> {
+    type Type = JsonRequestType<Self>;
+    type Response = JsonIdResponse<bool>;
+
+    fn serialize(&self) -> Result<HttpRequest, Error> {
+        Self::Type::serialize(RequestUrl::method("answerCallbackQuery"), self)
+    }
+}
+
+fn entity_tags(entity: &serde_json::Value) -> Option<(String, &'static str)> {
+    let tags = match entity["type"].as_str()? {
+        "bold" => ("<b>".to_string(), "</b>"),
+        "italic" => ("<i>".to_string(), "</i>"),
+        "underline" => ("<u>".to_string(), "</u>"),
+        "strikethrough" => ("<s>".to_string(), "</s>"),
+        "code" => ("<code>".to_string(), "</code>"),
+        "pre" => ("<pre>".to_string(), "</pre>"),
+        "text_link" => (format!(r#"<a href="{}">"#, htmlescape_str(entity["url"].as_str()?)), "</a>"),
+        "text_mention" => (format!(r#"<a href="tg://user?id={}">"#, entity["user"]["id"].as_i64()?), "</a>"),
+        // urls, mentions, hashtags etc. are recognized by Telegram itself
+        _ => return None,
+    };
+
+    Some(tags)
+}
+
+// HTML of a message, as needed to edit it without losing its
+// formatting. Entity offsets count UTF-16 code units.
+fn message_html(text: &str, entities: &[serde_json::Value]) -> String {
+    let spans = entities
+        .iter()
+        .filter_map(|entity| {
+            let start = entity["offset"].as_u64()? as usize;
+            let end = start + entity["length"].as_u64()? as usize;
+            let (open, close) = entity_tags(entity)?;
+
+            Some((start, end, open, close))
+        })
+        .collect::<Vec<(usize, usize, String, &str)>>();
+
+    let mut html = String::with_capacity(text.len());
+    // indices of the spans open at `pos`, innermost last
+    let mut open = Vec::<usize>::new();
+    let mut pos = 0;
+
+    for chr in text.chars() {
+        while let Some(idx) = open.last().cloned().filter(|idx| spans[*idx].1 <= pos) {
+            html.push_str(spans[idx].3);
+            open.pop();
+        }
+
+        let mut starting = (0..spans.len())
+            .filter(|idx| spans[*idx].0 == pos)
+            .collect::<Vec<usize>>();
+
+        // outermost first
+        starting.sort_by(|a, b| spans[*b].1.cmp(&spans[*a].1));
+
+        for idx in starting {
+            html.push_str(&spans[idx].2);
+            open.push(idx);
+        }
+
+        match chr {
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '&' => html.push_str("&amp;"),
+            '"' => html.push_str("&quot;"),
+            chr => html.push(chr),
+        }
+
+        pos += chr.len_utf16();
+    }
+
+    while let Some(idx) = open.pop() {
+        html.push_str(spans[idx].3);
+    }
+
+    html
+}
+
+// A press of an inline keyboard button. Decoded by hand, as
+// telegram-bot requires the message to have a sender, which
+// channel posts lack.
+struct ButtonPress {
+    id: String,
+    user_id: i64,
+    first_name: String,
+    chat_id: i64,
+    message_id: i64,
+    // None if the message isn't a text message
+    message_html: Option<String>,
+    data: String,
+}
+
+impl ButtonPress {
+    fn parse(query: &serde_json::Value) -> Option<ButtonPress> {
+        let message = &query["message"];
+
+        let message_html = message["text"].as_str().map(|text| {
+            message_html(text, message["entities"].as_array().map(|entities| entities.as_slice()).unwrap_or(&[]))
+        });
+
+        Some(ButtonPress {
+            id: query["id"].as_str()?.to_string(),
+            user_id: query["from"]["id"].as_i64()?,
+            first_name: query["from"]["first_name"].as_str().unwrap_or("").to_string(),
+            chat_id: message["chat"]["id"].as_i64()?,
+            message_id: message["message_id"].as_i64()?,
+            message_html,
+            data: query["data"].as_str().unwrap_or("").to_string(),
+        })
+    }
+}
+
+pub struct Bot {
+    configured_api: ConfiguredApi,
+    // one per configured wiki
+    wikis: Vec<WikiConfig>,
+    wiki_apis: Vec<WikiApiClient>,
+    control: Arc<Control>,
+    commands_enabled: bool,
+    accounts: Vec<ActionAccount>,
+    // by wiki and Telegram user
+    sessions: HashMap<(usize, i64), WikiSession>,
+    // user ids of the channel admins and when they were fetched
+    admins: Option<(u64, Vec<i64>)>,
+}
+
+impl Bot {
+    pub fn new(config: &Config, control: Arc<Control>) -> Bot {
+        let configured_api = ConfiguredApi::new(
+            "<b>EoP</b>",
+            telegram_bot::types::ParseMode::Html,
+            control.clone(),
+            None,
+        );
+
+        Bot {
+            configured_api,
+            wikis: config.mediawiki.wikis(),
+            wiki_apis: config.mediawiki
+                .wikis()
+                .iter()
+                .map(|wiki| WikiApiClient::new(&config.mediawiki, wiki))
+                .collect(),
+            control,
+            commands_enabled: config.telegram.commands,
+            accounts: config.mediawiki.actions.accounts.clone(),
+            sessions: HashMap::new(),
+            admins: None,
+        }
+    }
+
+    pub fn run(&mut self) {
+        let mut offset = 0;
+
+        loop {
+            let get_updates = GetRawUpdates {
+                offset,
+                timeout: POLL_TIMEOUT,
+            };
+
+            let updates_future = self.configured_api.api.send(get_updates);
+
+            let updates = match self.configured_api.core.borrow_mut().run(updates_future) {
+                Ok(updates) => updates,
+                Err(err) => {
+                    println!("couldn't obtain telegram updates: {:?}", err);
+
+                    std::thread::sleep(std::time::Duration::from_secs(5));
+
+                    continue;
+                }
+            };
+
+            for update in updates {
+                // updates that can't be handled are skipped all the same
+                if let Some(update_id) = update["update_id"].as_i64() {
+                    offset = update_id + 1;
+                }
+
+                if update["callback_query"].is_object() {
+                    match ButtonPress::parse(&update["callback_query"]) {
+                        Some(press) => self.handle_callback(&press),
+                        None => println!("couldn't decode telegram callback query: {}", update),
+                    }
+
+                    continue;
+                }
+
+                match serde_json::from_value::<Update>(update.clone()) {
+                    Ok(Update { kind: UpdateKind::Message(message), .. }) => self.handle_message(&message),
+                    Ok(Update { kind: UpdateKind::ChannelPost(post), .. }) => self.handle_channel_post(&post),
+                    Ok(_) => (),
+                    Err(err) => println!("couldn't decode telegram update {}: {}", update, err),
+                }
+            }
+        }
+    }
+
+    fn answer(&self, press: &ButtonPress, text: &str) {
+        let answer = AnswerButtonPress {
+            callback_query_id: &press.id,
+            text,
+        };
+
+        let answer_future = self.configured_api.api.send(answer);
+
+        let _ = self.configured_api.core.borrow_mut().run(answer_future);
+    }
+
+    fn handle_callback(&mut self, press: &ButtonPress) {
+        let (action, wiki_idx, rev_id, mut available) = match parse_callback_data(&press.data) {
+            Some(parsed) => parsed,
+            // e.g. the status rows of keyboards posted by older versions
+            None => return self.answer(press, ""),
+        };
+
+        let account = match self.accounts.iter().find(|account| account.telegram_user_id == press.user_id) {
+            Some(account) => account.clone(),
+            None => return self.answer(press, "You are not authorized to moderate the wiki from here."),
+        };
+
+        let wiki_api = match self.wiki_apis.get(wiki_idx) {
+            Some(wiki_api) => wiki_api,
+            None => return self.answer(press, "This wiki is no longer configured."),
+        };
+
+        let session = self.sessions
+            .entry((wiki_idx, press.user_id))
+            .or_insert_with(|| WikiSession::new(&account.wiki_user, &account.password));
+
+        if let Err(err) = wiki_api.perform(session, action, rev_id) {
+            println!(
+                "Action {:?} on revision {} by {} failed: {}",
+                action, rev_id, account.wiki_user, err
+            );
+
+            return self.answer(press, &format!("Failed: {}", err));
+        }
+
+        // bot password logins are named "<user>@<bot name>"
+        let wiki_user = account.wiki_user.split('@').next().unwrap_or("");
+
+        // a rollback supersedes everything else on the revision
+        match action {
+            WikiAction::Rollback => available.clear(),
+            _ => available.retain(|available| *available != action),
+        }
+
+        let keyboard = action_keyboard(wiki_idx, rev_id, &available);
+
+        match press.message_html {
+            Some(ref message_html) => {
+                let msg = format!(
+                    "{}\n✔ {} by {} ({})",
+                    message_html,
+                    action_past_tense(action),
+                    htmlescape_str(press.first_name.as_str()),
+                    htmlescape_str(wiki_user)
+                );
+
+                if self.configured_api.edit(press.chat_id, press.message_id, msg.clone(), Some(keyboard.clone())) {
+                    self.control.update_thread_message(press.chat_id, press.message_id, &msg, Some(keyboard));
+                }
+            }
+            None => {
+                let edit_markup = telegram_bot::EditMessageReplyMarkup::new(
+                    telegram_bot::ChannelId::new(press.chat_id),
+                    telegram_bot::MessageId::new(press.message_id),
+                    Some(keyboard),
+                );
+
+                let edit_future = self.configured_api.api.send(edit_markup);
+
+                let _ = self.configured_api.core.borrow_mut().run(edit_future);
+            }
+        }
+
+        self.answer(press, &format!("Revision {} {}", rev_id, action_past_tense(action)));
+    }
+
+    fn is_admin(&mut self, user_id: i64) -> bool {
+        let is_fresh = match self.admins {
+            Some((fetched_at, _)) => now_secs().saturating_sub(fetched_at) < ADMINS_TTL,
+            None => false,
+        };
+
+        if !is_fresh {
+            let channel = telegram_bot::ChannelId::new(self.configured_api.channel_id);
+
+            let admins_future = self.configured_api.api.send(channel.get_administrators());
+
+            match self.configured_api.core.borrow_mut().run(admins_future) {
+                Ok(members) => {
+                    let admin_ids = members
+                        .iter()
+                        .map(|member| i64::from(member.user.id))
+                        .collect();
+
+                    self.admins = Some((now_secs(), admin_ids));
+                }
+                Err(err) => println!("couldn't obtain channel admins: {:?}", err),
+            }
+        }
+
+        match self.admins {
+            Some((_, ref admin_ids)) => admin_ids.contains(&user_id),
+            None => false,
+        }
+    }
+
+    fn reply<M: CanReplySendMessage>(&self, message: &M, text: String) {
+        let mut reply = message.text_reply(text);
+
+        reply
+            .parse_mode(telegram_bot::types::ParseMode::Html)
+            .disable_preview();
+
+        let reply_future = self.configured_api.api.send(reply);
+
+        let _ = self.configured_api.core.borrow_mut().run(reply_future);
+    }
+
+    fn handle_message(&mut self, message: &Message) {
+        let text = match message.kind {
+            MessageKind::Text { ref data, .. } => data,
+            _ => return,
+        };
+
+        let is_private = matches!(message.chat, MessageChat::Private(_));
+
+        if let Some(response) = self.handle_command(text, Some((&message.from, is_private))) {
+            self.reply(message, response);
+        }
+    }
+
+    // Only admins can post to a channel, so commands posted to the
+    // channel need no further checks. Posts carry no sender though.
+    fn handle_channel_post(&mut self, post: &ChannelPost) {
+        if i64::from(post.chat.id) != self.configured_api.channel_id {
+            return;
+        }
+
+        let text = match post.kind {
+            MessageKind::Text { ref data, .. } => data,
+            _ => return,
+        };
+
+        if let Some(response) = self.handle_command(text, None) {
+            self.reply(post, response);
+        }
+    }
+
+    // Response to a command, None if the text isn't one. `sender` is the
+    // user and whether the chat is a private one, None for channel posts.
+    fn handle_command(&mut self, text: &str, sender: Option<(&User, bool)>) -> Option<String> {
+        if !self.commands_enabled || !text.starts_with('/') {
+            return None;
+        }
+
+        let mut args = text.split_whitespace();
+
+        // commands may be addressed as "/mute@EoPBot" in groups
+        let command = args.next().unwrap_or("").split('@').next().unwrap_or("");
+
+        let known_command = matches!(
+            command,
+            "/status" | "/mute" | "/unmute" | "/stats" | "/lastdonation" | "/sources" | "/pending"
+                | "/watch" | "/unwatch"
+        );
+
+        if !known_command {
+            return None;
+        }
+
+        if let Some((user, _)) = sender {
+            if !self.is_admin(i64::from(user.id)) {
+                return Some("Only admins of the channel can use commands.".to_string());
+            }
+        }
+
+        let response = match (command, sender) {
+            ("/status", _) => self.command_status(),
+            ("/mute", _) => self.command_mute(args.next(), args.next()),
+            ("/unmute", _) => self.command_unmute(args.next()),
+            ("/stats", _) => self.command_stats(args.next()),
+            ("/lastdonation", _) => self.control
+                .last_donation()
+                .unwrap_or_else(|| "No donation received since startup.".to_string()),
+            ("/watch", Some((user, is_private))) => {
+                self.command_watch(user, is_private, &args.collect::<Vec<&str>>().join(" "))
+            }
+            ("/unwatch", Some((user, _))) => self.command_unwatch(user, &args.collect::<Vec<&str>>().join(" ")),
+            ("/watch", None) | ("/unwatch", None) => {
+                "Watches are personal, send the command to the bot instead.".to_string()
+            }
+            ("/pending", _) => match self.control.approvals().is_enabled() {
+                true => self.control.approvals().render(&self.wikis),
+                false => "Approval tracking is disabled.".to_string(),
+            },
+            _ => self.command_sources(),
+        };
+
+        Some(response)
+    }
+
+    fn command_status(&self) -> String {
+        let muted = Source::ALL
+            .iter()
+            .filter_map(|source| {
+                self.control
+                    .muted_for(*source)
+                    .map(|secs| format!("{} (for {})", source.name(), format_duration(secs)))
+            })
+            .collect::<Vec<String>>();
+
+        let posted_today = Source::ALL
+            .iter()
+            .map(|source| self.control.posted(*source, 1))
+            .sum::<u64>();
+
+        format!(
+            "Up for {}, {} messages posted today. Muted: {}",
+            format_duration(self.control.uptime()),
+            posted_today,
+            match muted.is_empty() {
+                true => "none".to_string(),
+                false => muted.join(", "),
+            }
+        )
+    }
+
+    fn command_mute(&self, source: Option<&str>, duration: Option<&str>) -> String {
+        let sources = match parse_sources(source) {
+            Some(sources) => sources,
+            None => return "Usage: /mute &lt;source|all&gt; [duration, e.g. 30m, 1h, 2d]".to_string(),
+        };
+
+        let secs = match duration.map(parse_duration) {
+            None => DEFAULT_MUTE_SECS,
+            Some(Some(secs)) => secs,
+            Some(None) => return format!("Invalid duration: {}", duration.unwrap_or("")),
+        };
+
+        for source in &sources {
+            self.control.mute(*source, secs);
+        }
+
+        format!(
+            "Muted {} for {}.",
+            sources.iter().map(|source| source.name()).collect::<Vec<&str>>().join(", "),
+            format_duration(secs)
+        )
+    }
+
+    fn command_unmute(&self, source: Option<&str>) -> String {
+        let sources = match parse_sources(source) {
+            Some(sources) => sources,
+            None => return "Usage: /unmute [source|all]".to_string(),
+        };
+
+        for source in &sources {
+            self.control.unmute(*source);
+        }
+
+        format!(
+            "Unmuted {}.",
+            sources.iter().map(|source| source.name()).collect::<Vec<&str>>().join(", ")
+        )
+    }
+
+    fn command_stats(&self, period: Option<&str>) -> String {
+        let period = period.unwrap_or("today");
+
+        let counts = Source::ALL
+            .iter()
+            .map(|source| {
+                let posted = match period {
+                    "yesterday" => self.control.posted_yesterday(*source),
+                    "week" => self.control.posted(*source, 7),
+                    _ => self.control.posted(*source, 1),
+                };
+
+                format!("{}: <b>{}</b>", source.name(), posted)
+            })
+            .collect::<Vec<String>>();
+
+        let muted = Source::ALL
+            .iter()
+            .map(|source| self.control.muted_count(*source))
+            .sum::<u64>();
+
+        let suppressions = self.control.suppressions()
+            .iter()
+            .map(|(rule, count)| format!("{} ({})", htmlescape_str(rule.as_str()), count))
+            .collect::<Vec<String>>();
+
+        format!(
+            "Messages posted ({}): {}; {} dropped while muted since startup{}",
+            match period {
+                "yesterday" | "week" => period,
+                _ => "today",
+            },
+            counts.join(", "),
+            muted,
+            match suppressions.is_empty() {
+                true => String::new(),
+                false => format!("\nSuppression rules matched since startup: {}", suppressions.join(", ")),
+            }
+        )
+    }
+
+    fn command_watch(&self, user: &User, is_private: bool, title: &str) -> String {
+        let user_id = i64::from(user.id);
+        let watchlist = self.control.watchlist();
+
+        if title.is_empty() {
+            let watched = watchlist.watched_by(user_id);
+
+            return match watched.is_empty() {
+                true => "Usage: /watch &lt;page|Category:name&gt;".to_string(),
+                false => format!("Watching: {}", htmlescape_str(watched.join(", "))),
+            };
+        }
+
+        let watcher = Watcher {
+            telegram_user_id: user_id,
+            username: user.username.clone(),
+            name: Some(user.first_name.clone()),
+            // watches set up in a private chat are notified there
+            dm: is_private,
+            ..Watcher::default()
+        };
+
+        match watchlist.watch(watcher, title) {
+            true => format!("Watching {}.", htmlescape_str(title)),
+            false => format!("Already watching {}.", htmlescape_str(title)),
+        }
+    }
+
+    fn command_unwatch(&self, user: &User, title: &str) -> String {
+        if title.is_empty() {
+            return "Usage: /unwatch &lt;page|Category:name&gt;".to_string();
+        }
+
+        match self.control.watchlist().unwatch(i64::from(user.id), title) {
+            true => format!("No longer watching {}.", htmlescape_str(title)),
+            false => format!("Not watching {} (configured watches can't be removed).", htmlescape_str(title)),
+        }
+    }
+
+    fn command_sources(&self) -> String {
+        Source::ALL
+            .iter()
+            .map(|source| {
+                format!(
+                    "<b>{}</b> on {}, last event: {}{}",
+                    source.name(),
+                    source_endpoint(*source),
+                    match self.control.last_event(*source) {
+                        Some(last_event) => format!("{} ago", format_duration(now_secs().saturating_sub(last_event))),
+                        None => "none since startup".to_string(),
+                    },
+                    match self.control.muted_for(*source) {
+                        Some(secs) => format!(" (muted for {})", format_duration(secs)),
+                        None => String::new(),
+                    }
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restores_message_html() {
+        let entities = serde_json::json!([
+            { "type": "bold", "offset": 2, "length": 3 },
+            { "type": "text_link", "offset": 8, "length": 9, "url": "https://example.org/?a=1&b=2" },
+            { "type": "italic", "offset": 11, "length": 6 },
+            { "type": "mention", "offset": 18, "length": 4 },
+        ]);
+
+        assert_eq!(
+            message_html("⥂ EoP ⟹ 🙂 <Page> @bob", entities.as_array().unwrap()),
+            "⥂ <b>EoP</b> ⟹ <a href=\"https://example.org/?a=1&amp;b=2\">🙂 <i>&lt;Page&gt;</i></a> @bob"
+        );
+    }
+
+    #[test]
+    fn parses_callback_data() {
+        let (action, wiki_idx, rev_id, available) = parse_callback_data("r:1234:pra:1").unwrap();
+
+        assert_eq!(action, WikiAction::Rollback);
+        assert_eq!((wiki_idx, rev_id), (1, 1234));
+        assert_eq!(available, vec![WikiAction::Patrol, WikiAction::Rollback, WikiAction::Approve]);
+
+        assert_eq!(parse_callback_data("p:1234:pa").map(|parsed| parsed.1), Some(0));
+        assert!(parse_callback_data("-").is_none());
+    }
+}
@@ -0,0 +1,518 @@
+/*
+ * CONFIGURATION
+ *
+ * Optional JSON configuration, loaded from the file named by the
+ * `EOP_CONFIG` environment variable. Every field has a default, so
+ * an absent file (or an absent key) keeps the built-in behaviour.
+ */
+
+use std::{
+    collections::HashMap,
+    fs,
+    net::IpAddr,
+};
+
+const PW_API_URL_PREFIX: &str = "https://psychonautwiki.org/w/api.php";
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    pub mediawiki: MediaWikiConfig,
+    pub telegram: TelegramConfig,
+    pub digest: DigestConfig,
+    pub github: GithubConfig,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct TelegramConfig {
+    // answer bot commands from admins of the channel, sent in private
+    // or group chats with the bot or posted to the channel itself
+    pub commands: bool,
+    pub threading: ThreadingConfig,
+}
+
+// What to do with follow-up events on an entity already posted about
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ThreadingMode {
+    // post a new top-level message
+    #[default]
+    Off,
+    // reply to the first message
+    Reply,
+    // append to the text of the first message
+    Edit,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct ThreadingConfig {
+    pub mediawiki: ThreadingMode,
+    pub github: ThreadingMode,
+    pub jira: ThreadingMode,
+    // number of entities whose messages are remembered
+    pub cache_size: usize,
+}
+
+impl Default for ThreadingConfig {
+    fn default() -> ThreadingConfig {
+        ThreadingConfig {
+            mediawiki: ThreadingMode::Off,
+            github: ThreadingMode::Off,
+            jira: ThreadingMode::Off,
+            cache_size: 1024,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct GithubConfig {
+    // commits listed in push notifications, further ones are counted
+    pub push_commits: usize,
+    // announce pushed tags; releases are announced either way
+    pub tag_pushes: bool,
+    // pull-request actions announced although they are noisy, out of
+    // "labeled", "unlabeled", "assigned", "unassigned", "review_requested",
+    // "review_request_removed" and "synchronize"
+    pub pull_request_actions: Vec<String>,
+}
+
+impl Default for GithubConfig {
+    fn default() -> GithubConfig {
+        GithubConfig {
+            push_commits: 5,
+            tag_pushes: true,
+            pull_request_actions: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestInterval {
+    Hourly,
+    Daily,
+}
+
+// Streams of low-priority events summarized periodically instead of
+// posted one by one, e.g.
+//
+//     "digest": {
+//         "interval": "daily",
+//         "streams": ["mediawiki:minor_edit", "mediawiki:log/thanks", "github:star", "github:fork"]
+//     }
+//
+// Stream names are "<source>:<kind>", see `/sources` for the sources.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct DigestConfig {
+    pub streams: Vec<String>,
+    pub interval: DigestInterval,
+    // chat to post digests to instead of the main channel
+    pub channel_id: Option<i64>,
+    // number of most active pages and users listed
+    pub top: usize,
+    // file the pending digest is kept in across restarts
+    pub state_path: String,
+}
+
+impl Default for DigestConfig {
+    fn default() -> DigestConfig {
+        DigestConfig {
+            streams: Vec::new(),
+            interval: DigestInterval::Daily,
+            channel_id: None,
+            top: 5,
+            state_path: "eop-digest.json".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct MediaWikiConfig {
+    // base url of the api of the default wiki, used when no `wikis`
+    // are configured; override to point at a local stand-in
+    pub api_url: String,
+    pub wikis: Vec<WikiConfig>,
+    pub api_timeout_secs: u64,
+    pub revision_cache_size: usize,
+    // how long to wait for further events before handling a burst
+    pub batch_window_ms: u64,
+    // category membership changes, off by default
+    pub categorize_enabled: bool,
+    // chat to post them to instead of the main channel
+    pub categorize_channel_id: Option<i64>,
+    // highlight edits removing more than this share of a page
+    pub large_removal_percent: u32,
+    // append the first changed lines of the diff to edit notifications
+    pub diff_excerpts: bool,
+    pub diff_excerpt_lines: usize,
+    pub diff_excerpt_line_length: usize,
+    // send a thumbnail of uploaded files along with the notification
+    pub upload_previews: bool,
+    pub upload_preview_width: u32,
+    pub namespaces: NamespaceConfig,
+    pub suppression: SuppressionConfig,
+    pub watchlist: WatchlistConfig,
+    pub risk: RiskConfig,
+    pub actions: ActionsConfig,
+    pub anon: AnonConfig,
+    pub approvals: ApprovalsConfig,
+}
+
+impl Default for MediaWikiConfig {
+    fn default() -> MediaWikiConfig {
+        MediaWikiConfig {
+            api_url: PW_API_URL_PREFIX.to_string(),
+            wikis: Vec::new(),
+            api_timeout_secs: 10,
+            revision_cache_size: 512,
+            batch_window_ms: 500,
+            categorize_enabled: false,
+            categorize_channel_id: None,
+            large_removal_percent: 50,
+            diff_excerpts: false,
+            diff_excerpt_lines: 4,
+            diff_excerpt_line_length: 160,
+            upload_previews: false,
+            upload_preview_width: 640,
+            namespaces: NamespaceConfig::default(),
+            suppression: SuppressionConfig::default(),
+            watchlist: WatchlistConfig::default(),
+            risk: RiskConfig::default(),
+            actions: ActionsConfig::default(),
+            anon: AnonConfig::default(),
+            approvals: ApprovalsConfig::default(),
+        }
+    }
+}
+
+impl MediaWikiConfig {
+    // Configured wikis, or the default wiki if there are none
+    pub fn wikis(&self) -> Vec<WikiConfig> {
+        let wikis = match self.wikis.is_empty() {
+            true => vec![WikiConfig {
+                api_url: self.api_url.clone(),
+                ..WikiConfig::default()
+            }],
+            false => self.wikis.clone(),
+        };
+
+        wikis.into_iter().map(WikiConfig::with_derived_urls).collect()
+    }
+}
+
+// A wiki whose recent changes are reported. Events are attributed to
+// the first wiki whose criteria all match, e.g.
+//
+//     "wikis": [
+//         { "label": "de", "server_name": "de.psychonautwiki.org",
+//           "api_url": "https://de.psychonautwiki.org/w/api.php",
+//           "article_url": "https://de.psychonautwiki.org/wiki",
+//           "index_url": "https://de.psychonautwiki.org/w/index.php" },
+//         { "port": 3001, ... }
+//     ]
+//
+// Article and index urls left out are derived from the api url.
+// A wiki without any criteria takes all remaining events.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct WikiConfig {
+    // shown next to the emitter name, none if empty
+    pub label: String,
+    // UDP port the wiki sends its events to, the default port if unset
+    pub port: Option<u16>,
+    // address the events are sent from
+    pub source_address: Option<IpAddr>,
+    // `server_name` and `wiki` (database name) fields of the events
+    pub server_name: Option<String>,
+    pub wiki: Option<String>,
+    pub api_url: String,
+    pub article_url: String,
+    pub index_url: String,
+}
+
+impl Default for WikiConfig {
+    fn default() -> WikiConfig {
+        WikiConfig {
+            label: String::new(),
+            port: None,
+            source_address: None,
+            server_name: None,
+            wiki: None,
+            api_url: PW_API_URL_PREFIX.to_string(),
+            article_url: String::new(),
+            index_url: String::new(),
+        }
+    }
+}
+
+impl WikiConfig {
+    // Assumes the usual layout of "<script path>/api.php", with articles
+    // under "/wiki" if the script path is "/w", and "index.php/" otherwise
+    fn with_derived_urls(mut self) -> WikiConfig {
+        let script_path = self.api_url
+            .strip_suffix("/api.php")
+            .unwrap_or(&self.api_url)
+            .to_string();
+
+        if self.index_url.is_empty() {
+            self.index_url = format!("{}/index.php", script_path);
+        }
+
+        if self.article_url.is_empty() {
+            self.article_url = match script_path.strip_suffix("/w") {
+                Some(server) => format!("{}/wiki", server),
+                None => format!("{}/index.php", script_path),
+            };
+        }
+
+        self
+    }
+}
+
+// Tracking of edits awaiting approval (Approved Revs extension)
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct ApprovalsConfig {
+    pub enabled: bool,
+    // namespaces whose pages need approval
+    pub namespaces: Vec<i64>,
+    // UTC hour the daily report of pending pages is posted at
+    pub report_hour: u8,
+    // pages listed in reports and by `/pending`
+    pub report_limit: usize,
+    // file the pending pages are kept in across restarts
+    pub state_path: String,
+}
+
+impl Default for ApprovalsConfig {
+    fn default() -> ApprovalsConfig {
+        ApprovalsConfig {
+            enabled: false,
+            namespaces: vec![0],
+            report_hour: 9,
+            report_limit: 15,
+            state_path: "eop-approvals.json".to_string(),
+        }
+    }
+}
+
+// Edits by IP addresses and temporary accounts
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct AnonConfig {
+    // names of temporary accounts start with this, e.g. "~2024-123"
+    pub temp_account_prefix: String,
+    // MaxMind GeoIP2 or GeoLite2 country database, to annotate IP edits
+    pub geoip_database: Option<String>,
+    // IP edits and page creations beyond this many per range and window
+    // are held back and summarized, 0 disables the limit
+    pub flood_max_edits: usize,
+    pub flood_window_secs: u64,
+    // prefix lengths of the ranges edits are grouped by
+    pub flood_ipv4_prefix: u8,
+    pub flood_ipv6_prefix: u8,
+}
+
+impl Default for AnonConfig {
+    fn default() -> AnonConfig {
+        AnonConfig {
+            temp_account_prefix: "~".to_string(),
+            geoip_database: None,
+            flood_max_edits: 0,
+            flood_window_secs: 600,
+            flood_ipv4_prefix: 24,
+            flood_ipv6_prefix: 64,
+        }
+    }
+}
+
+// Filtering and routing on the namespace id of recent changes; the
+// filter applies to edits, page creations and category changes, e.g.
+//
+//     "namespaces": {
+//         "exclude": [2, 3],
+//         "routes": { "0": { "notify": true }, "10": { "channel_id": -100123 } }
+//     }
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct NamespaceConfig {
+    // only these namespaces are reported, all if empty
+    pub include: Vec<i64>,
+    pub exclude: Vec<i64>,
+    // also filter log entries, which are logged against e.g. the user
+    // page of a blocked or newly registered user
+    pub filter_logs: bool,
+    pub routes: HashMap<i64, NamespaceRoute>,
+}
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct NamespaceRoute {
+    pub channel_id: Option<i64>,
+    // overrides whether messages trigger a notification
+    pub notify: Option<bool>,
+}
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct SuppressionConfig {
+    pub ignore_bots: bool,
+    // e.g. "Maintenance script"
+    pub ignore_users: Vec<String>,
+    // regular expressions matched against the full page title
+    pub ignore_titles: Vec<String>,
+    // post minor or patrolled edits without notification
+    pub silent_minor: bool,
+    pub silent_patrolled: bool,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct WatchlistConfig {
+    pub watchers: Vec<Watcher>,
+    // file the watchers subscribed with `/watch` are kept in
+    pub state_path: String,
+}
+
+impl Default for WatchlistConfig {
+    fn default() -> WatchlistConfig {
+        WatchlistConfig {
+            watchers: Vec::new(),
+            state_path: "eop-watchlist.json".to_string(),
+        }
+    }
+}
+
+// A Telegram user subscribed to changes of pages or categories
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct Watcher {
+    pub telegram_user_id: i64,
+    pub username: Option<String>,
+    pub name: Option<String>,
+    pub pages: Vec<String>,
+    pub categories: Vec<String>,
+    // send a direct message instead of mentioning in the channel
+    pub dm: bool,
+}
+
+// Scoring of edits and page creations, each matching heuristic adds
+// its score. Large removals use `large_removal_percent`.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct RiskConfig {
+    pub enabled: bool,
+    // fetch the diff of edits and the content of new pages to look for
+    // links and blacklisted words
+    pub inspect_diff: bool,
+    pub anon_score: u32,
+    pub new_account_score: u32,
+    // accounts registered within this window count as new
+    pub new_account_hours: u32,
+    pub blanking_score: u32,
+    pub external_link_score: u32,
+    pub blacklist_score: u32,
+    pub blacklist: Vec<String>,
+    pub medium_threshold: u32,
+    pub high_threshold: u32,
+    // high-risk changes are additionally posted here, with notification
+    pub moderators_channel_id: Option<i64>,
+}
+
+impl Default for RiskConfig {
+    fn default() -> RiskConfig {
+        RiskConfig {
+            enabled: false,
+            inspect_diff: false,
+            anon_score: 2,
+            new_account_score: 2,
+            new_account_hours: 24,
+            blanking_score: 3,
+            external_link_score: 2,
+            blacklist_score: 3,
+            blacklist: Vec::new(),
+            medium_threshold: 3,
+            high_threshold: 5,
+            moderators_channel_id: None,
+        }
+    }
+}
+
+// Inline buttons to patrol, rollback or approve revisions from the chat
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct ActionsConfig {
+    pub enabled: bool,
+    // Telegram users allowed to press the buttons
+    pub accounts: Vec<ActionAccount>,
+}
+
+// Maps a Telegram user to the wiki account acting on their behalf,
+// using a bot password (Special:BotPasswords)
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct ActionAccount {
+    pub telegram_user_id: i64,
+    // "<user>@<bot password name>"
+    pub wiki_user: String,
+    pub password: String,
+}
+
+impl Config {
+    pub fn load() -> Config {
+        let path = match std::env::var("EOP_CONFIG") {
+            Ok(path) => path,
+            Err(_) => return Config::default(),
+        };
+
+        let raw = fs::read_to_string(&path)
+            .unwrap_or_else(|err| panic!("✘ Could not read config file {}: {}", path, err));
+
+        serde_json::from_str(&raw)
+            .unwrap_or_else(|err| panic!("✘ Could not parse config file {}: {}", path, err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wiki(api_url: &str) -> WikiConfig {
+        WikiConfig {
+            api_url: api_url.to_string(),
+            ..WikiConfig::default()
+        }
+        .with_derived_urls()
+    }
+
+    #[test]
+    fn derives_urls_from_api_url() {
+        let default_wiki = &MediaWikiConfig::default().wikis()[0];
+
+        assert_eq!(default_wiki.article_url, "https://psychonautwiki.org/wiki");
+        assert_eq!(default_wiki.index_url, "https://psychonautwiki.org/w/index.php");
+
+        let other_wiki = wiki("https://example.org/mediawiki/api.php");
+
+        assert_eq!(other_wiki.article_url, "https://example.org/mediawiki/index.php");
+        assert_eq!(other_wiki.index_url, "https://example.org/mediawiki/index.php");
+    }
+
+    #[test]
+    fn keeps_configured_urls() {
+        let configured = WikiConfig {
+            api_url: "https://example.org/w/api.php".to_string(),
+            article_url: "https://example.org/page".to_string(),
+            ..WikiConfig::default()
+        }
+        .with_derived_urls();
+
+        assert_eq!(configured.article_url, "https://example.org/page");
+        assert_eq!(configured.index_url, "https://example.org/w/index.php");
+    }
+}
@@ -0,0 +1,318 @@
+/*
+ * RUNTIME CONTROL AND STATISTICS
+ *
+ * State shared between the emitters and the Telegram bot commands.
+ */
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::approvals::ApprovalTracker;
+use crate::config::{Config, ThreadingConfig, ThreadingMode};
+use crate::digest::Digest;
+use crate::threads::{MessageThreads, Thread};
+use crate::watchlist::Watchlist;
+
+const SECS_PER_DAY: u64 = 86400;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Source {
+    MediaWiki,
+    GitHub,
+    Jira,
+    PayPal,
+}
+
+impl Source {
+    pub const ALL: [Source; 4] = [Source::MediaWiki, Source::GitHub, Source::Jira, Source::PayPal];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Source::MediaWiki => "mediawiki",
+            Source::GitHub => "github",
+            Source::Jira => "jira",
+            Source::PayPal => "paypal",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Source> {
+        Source::ALL
+            .iter()
+            .cloned()
+            .find(|source| source.name().eq_ignore_ascii_case(name))
+    }
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+// 3725 -> "1h 2m"
+pub fn format_duration(secs: u64) -> String {
+    match secs {
+        secs if secs >= SECS_PER_DAY => format!("{}d {}h", secs / SECS_PER_DAY, secs % SECS_PER_DAY / 3600),
+        secs if secs >= 3600 => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+        secs if secs >= 60 => format!("{}m", secs / 60),
+        secs => format!("{}s", secs),
+    }
+}
+
+// "90s", "30m", "1h", "2d"; plain numbers are minutes
+pub fn parse_duration(duration: &str) -> Option<u64> {
+    let duration = duration.trim();
+
+    let (value, unit) = match duration.find(|chr: char| !chr.is_ascii_digit()) {
+        Some(idx) => duration.split_at(idx),
+        None => (duration, "m"),
+    };
+
+    let value = value.parse::<u64>().ok()?;
+
+    match unit {
+        "s" => Some(value),
+        "m" => value.checked_mul(60),
+        "h" => value.checked_mul(3600),
+        "d" => value.checked_mul(SECS_PER_DAY),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+struct SourceState {
+    // unix timestamp until which messages are dropped
+    muted_until: Option<u64>,
+    last_event: Option<u64>,
+    // messages posted, per day since the epoch
+    posted: HashMap<u64, u64>,
+    muted: u64,
+}
+
+pub struct Control {
+    started_at: u64,
+    sources: Mutex<HashMap<Source, SourceState>>,
+    last_donation: Mutex<Option<String>>,
+    threading: ThreadingConfig,
+    // emitters are short-lived, so the messages posted per entity are kept here
+    threads: Mutex<MessageThreads>,
+    digest: Digest,
+    approvals: ApprovalTracker,
+    // shared by the wikis and the `/watch` command
+    watchlist: Watchlist,
+    // events matched per suppression rule, since startup
+    suppressions: Mutex<BTreeMap<String, u64>>,
+}
+
+impl Control {
+    pub fn new(config: &Config) -> Control {
+        let threading = &config.telegram.threading;
+
+        Control {
+            started_at: now_secs(),
+            sources: Mutex::new(HashMap::new()),
+            last_donation: Mutex::new(None),
+            threading: threading.clone(),
+            threads: Mutex::new(MessageThreads::new(threading.cache_size)),
+            digest: Digest::new(&config.digest),
+            approvals: ApprovalTracker::new(&config.mediawiki.approvals),
+            watchlist: Watchlist::new(&config.mediawiki.watchlist),
+            suppressions: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn digest(&self) -> &Digest {
+        &self.digest
+    }
+
+    pub fn approvals(&self) -> &ApprovalTracker {
+        &self.approvals
+    }
+
+    pub fn watchlist(&self) -> &Watchlist {
+        &self.watchlist
+    }
+
+    pub fn uptime(&self) -> u64 {
+        now_secs().saturating_sub(self.started_at)
+    }
+
+    pub fn mute(&self, source: Source, secs: u64) {
+        let mut sources = self.sources.lock().unwrap();
+
+        sources.entry(source).or_default().muted_until = Some(now_secs().saturating_add(secs));
+    }
+
+    pub fn unmute(&self, source: Source) {
+        let mut sources = self.sources.lock().unwrap();
+
+        sources.entry(source).or_default().muted_until = None;
+    }
+
+    // Remaining mute time of a source in seconds, if muted
+    pub fn muted_for(&self, source: Source) -> Option<u64> {
+        let sources = self.sources.lock().unwrap();
+
+        let muted_until = sources.get(&source)?.muted_until?;
+
+        muted_until.checked_sub(now_secs()).filter(|secs| *secs > 0)
+    }
+
+    // Account for a message about to be posted, returns false if the
+    // source is muted and the message should be dropped.
+    pub fn record(&self, source: Source) -> bool {
+        let muted = self.muted_for(source).is_some();
+
+        let now = now_secs();
+
+        let mut sources = self.sources.lock().unwrap();
+        let state = sources.entry(source).or_default();
+
+        state.last_event = Some(now);
+
+        if muted {
+            state.muted += 1;
+        } else {
+            *state.posted.entry(now / SECS_PER_DAY).or_insert(0) += 1;
+        }
+
+        !muted
+    }
+
+    pub fn last_event(&self, source: Source) -> Option<u64> {
+        self.sources.lock().unwrap().get(&source)?.last_event
+    }
+
+    // Messages posted by a source during the last `days` days, including today
+    pub fn posted(&self, source: Source, days: u64) -> u64 {
+        let today = now_secs() / SECS_PER_DAY;
+
+        match self.sources.lock().unwrap().get(&source) {
+            Some(state) => state.posted
+                .iter()
+                .filter(|(day, _)| today.saturating_sub(**day) < days)
+                .map(|(_, count)| count)
+                .sum(),
+            None => 0,
+        }
+    }
+
+    // Messages posted by a source yesterday
+    pub fn posted_yesterday(&self, source: Source) -> u64 {
+        let yesterday = (now_secs() / SECS_PER_DAY).saturating_sub(1);
+
+        self.sources.lock().unwrap()
+            .get(&source)
+            .and_then(|state| state.posted.get(&yesterday).cloned())
+            .unwrap_or(0)
+    }
+
+    pub fn muted_count(&self, source: Source) -> u64 {
+        self.sources.lock().unwrap()
+            .get(&source)
+            .map(|state| state.muted)
+            .unwrap_or(0)
+    }
+
+    // Account for an event matching a suppression rule, returns the
+    // number of matches of the rule so far
+    pub fn record_suppression(&self, rule: &str) -> u64 {
+        let mut suppressions = self.suppressions.lock().unwrap();
+
+        let counter = suppressions.entry(rule.to_string()).or_insert(0);
+
+        *counter += 1;
+
+        *counter
+    }
+
+    pub fn suppressions(&self) -> Vec<(String, u64)> {
+        self.suppressions.lock().unwrap()
+            .iter()
+            .map(|(rule, count)| (rule.clone(), *count))
+            .collect()
+    }
+
+    pub fn set_last_donation(&self, msg: String) {
+        *self.last_donation.lock().unwrap() = Some(msg);
+    }
+
+    pub fn last_donation(&self) -> Option<String> {
+        self.last_donation.lock().unwrap().clone()
+    }
+
+    pub fn threading(&self, source: Source) -> ThreadingMode {
+        match source {
+            Source::MediaWiki => self.threading.mediawiki,
+            Source::GitHub => self.threading.github,
+            Source::Jira => self.threading.jira,
+            Source::PayPal => ThreadingMode::Off,
+        }
+    }
+
+    // The first message posted about an entity
+    pub fn thread(&self, key: &str) -> Option<Thread> {
+        self.threads.lock().unwrap().get(key)
+    }
+
+    pub fn remember_thread(&self, key: &str, thread: Thread) {
+        self.threads.lock().unwrap().insert(key.to_string(), thread);
+    }
+
+    // Keep threads current after a message was edited by other means
+    pub fn update_thread_message(
+        &self,
+        chat_id: i64,
+        message_id: i64,
+        text: &str,
+        reply_markup: Option<telegram_bot::InlineKeyboardMarkup>,
+    ) {
+        let mut threads = self.threads.lock().unwrap();
+
+        let thread = threads
+            .values_mut()
+            .find(|thread| thread.chat_id == chat_id && thread.message_id == message_id);
+
+        if let Some(thread) = thread {
+            thread.text = text.to_string();
+            thread.reply_markup = reply_markup;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(0), "0s");
+        assert_eq!(format_duration(59), "59s");
+        assert_eq!(format_duration(90), "1m");
+        assert_eq!(format_duration(3725), "1h 2m");
+        assert_eq!(format_duration(2 * SECS_PER_DAY + 7200), "2d 2h");
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90s"), Some(90));
+        assert_eq!(parse_duration("30m"), Some(1800));
+        assert_eq!(parse_duration(" 1h "), Some(3600));
+        assert_eq!(parse_duration("2d"), Some(2 * SECS_PER_DAY));
+        assert_eq!(parse_duration("15"), Some(900));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration("1w"), None);
+        assert_eq!(parse_duration("1.5h"), None);
+        assert_eq!(parse_duration("-1m"), None);
+        assert_eq!(parse_duration("99999999999999999d"), None);
+    }
+}
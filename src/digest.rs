@@ -0,0 +1,265 @@
+/*
+ * PERIODIC DIGESTS
+ *
+ * Events of low-priority streams are only counted, and summarized in
+ * one message per interval. The pending digest is written to disk
+ * about once a minute so it survives restarts.
+ */
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use crate::config::{DigestConfig, DigestInterval};
+use crate::control::{now_secs, Source};
+use crate::{htmlescape_str, save_state};
+
+#[derive(Serialize, Deserialize, Default)]
+struct SourceDigest {
+    // events per stream kind, e.g. "minor_edit"
+    streams: BTreeMap<String, u64>,
+    users: HashMap<String, u64>,
+    subjects: HashMap<String, u64>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct DigestState {
+    period_start: u64,
+    // keyed by source name
+    sources: BTreeMap<String, SourceDigest>,
+}
+
+pub struct Digest {
+    config: DigestConfig,
+    state: Mutex<DigestState>,
+    // changed since last saved
+    dirty: AtomicBool,
+}
+
+fn interval_secs(interval: DigestInterval) -> u64 {
+    match interval {
+        DigestInterval::Hourly => 3600,
+        DigestInterval::Daily => 86400,
+    }
+}
+
+// "minor_edit" -> "minor edits"
+fn stream_label(source: Source, kind: &str) -> String {
+    let label = match (source, kind) {
+        (Source::MediaWiki, "edit") => "edits",
+        (Source::MediaWiki, "minor_edit") => "minor edits",
+        (Source::MediaWiki, "bot_edit") => "bot edits",
+        (Source::MediaWiki, "new") => "new pages",
+        (Source::MediaWiki, "categorize") => "category changes",
+        (Source::MediaWiki, "log/upload") => "uploads",
+        (Source::MediaWiki, "log/thanks") => "thanks",
+        (Source::MediaWiki, "log/newusers") => "new accounts",
+        (Source::GitHub, "star") => "stars",
+        (Source::GitHub, "fork") => "forks",
+        (Source::GitHub, "push") => "pushes",
+        (Source::GitHub, "create") => "created tags",
+        (Source::GitHub, "gollum") => "wiki edits",
+        (Source::GitHub, "status") | (Source::GitHub, "check_run") => "CI checks",
+        (Source::GitHub, "workflow_run") => "workflow runs",
+        (Source::GitHub, "deployment_status") => "deployment updates",
+        (Source::Jira, "issue_created") => "created issues",
+        (Source::Jira, "issue_updated") => "issue updates",
+        (Source::Jira, "issue_deleted") => "deleted issues",
+        (Source::PayPal, "donation") => "donations",
+        (_, kind) => return format!("{} events", kind.trim_start_matches("log/").replace('_', " ")),
+    };
+
+    label.to_string()
+}
+
+fn subject_label(source: Source) -> &'static str {
+    match source {
+        Source::MediaWiki => "pages",
+        Source::GitHub => "repositories",
+        Source::Jira => "issues",
+        Source::PayPal => "currencies",
+    }
+}
+
+// "Page (12), Other page (3)"
+fn top_entries(counts: &HashMap<String, u64>, top: usize) -> String {
+    let mut entries = counts.iter().collect::<Vec<(&String, &u64)>>();
+
+    entries.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+
+    entries
+        .iter()
+        .take(top)
+        .map(|(name, count)| format!("{} ({})", htmlescape_str(name.as_str()), count))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+impl Digest {
+    pub fn new(config: &DigestConfig) -> Digest {
+        let mut state = match fs::read_to_string(&config.state_path) {
+            Ok(raw) => serde_json::from_str(&raw).unwrap_or_else(|err| {
+                println!("couldn't parse digest state {}: {}", config.state_path, err);
+
+                DigestState::default()
+            }),
+            Err(_) => DigestState::default(),
+        };
+
+        if state.period_start == 0 {
+            state.period_start = Digest::current_period_start(config.interval);
+        }
+
+        Digest {
+            config: config.clone(),
+            state: Mutex::new(state),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    fn current_period_start(interval: DigestInterval) -> u64 {
+        let secs = interval_secs(interval);
+
+        now_secs() / secs * secs
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.config.streams.is_empty()
+    }
+
+    pub fn channel_id(&self) -> Option<i64> {
+        self.config.channel_id
+    }
+
+    fn is_digested(&self, source: Source, kind: &str) -> bool {
+        let stream = format!("{}:{}", source.name(), kind);
+
+        self.config.streams.iter().any(|digested| *digested == stream)
+    }
+
+    fn save(&self, state: &DigestState) {
+        match save_state(&self.config.state_path, state) {
+            Ok(_) => self.dirty.store(false, Ordering::SeqCst),
+            Err(err) => println!("couldn't save digest state {}: {}", self.config.state_path, err),
+        }
+    }
+
+    // Save the events recorded since the last call, if any
+    pub fn flush(&self) {
+        if !self.dirty.load(Ordering::SeqCst) {
+            return;
+        }
+
+        let state = self.state.lock().unwrap();
+
+        self.save(&state);
+    }
+
+    // Count an event towards the digest if its stream is digested;
+    // returns false if it should be posted as usual.
+    pub fn record(&self, source: Source, kind: &str, user: &str, subject: &str) -> bool {
+        if !self.is_digested(source, kind) {
+            return false;
+        }
+
+        let mut state = self.state.lock().unwrap();
+
+        {
+            let source_digest = state.sources.entry(source.name().to_string()).or_default();
+
+            *source_digest.streams.entry(kind.to_string()).or_insert(0) += 1;
+
+            if !user.is_empty() {
+                *source_digest.users.entry(user.to_string()).or_insert(0) += 1;
+            }
+
+            if !subject.is_empty() {
+                *source_digest.subjects.entry(subject.to_string()).or_insert(0) += 1;
+            }
+        }
+
+        self.dirty.store(true, Ordering::SeqCst);
+
+        true
+    }
+
+    fn render(&self, state: &DigestState) -> Option<String> {
+        let lines = Source::ALL
+            .iter()
+            .filter_map(|source| {
+                let source_digest = state.sources.get(source.name())?;
+
+                let counts = source_digest.streams
+                    .iter()
+                    .map(|(kind, count)| format!("{} {}", count, stream_label(*source, kind)))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+
+                let mut line = format!(
+                    "<b>{}</b>: {} by {} users across {} {}",
+                    source.name(),
+                    counts,
+                    source_digest.users.len(),
+                    source_digest.subjects.len(),
+                    subject_label(*source)
+                );
+
+                if !source_digest.subjects.is_empty() {
+                    line.push_str(&format!(
+                        "\n  top {}: {}",
+                        subject_label(*source),
+                        top_entries(&source_digest.subjects, self.config.top)
+                    ));
+                }
+
+                if !source_digest.users.is_empty() {
+                    line.push_str(&format!(
+                        "\n  top users: {}",
+                        top_entries(&source_digest.users, self.config.top)
+                    ));
+                }
+
+                Some(line)
+            })
+            .collect::<Vec<String>>();
+
+        if lines.is_empty() {
+            return None;
+        }
+
+        Some(format!(
+            "{}:\n{}",
+            match self.config.interval {
+                DigestInterval::Hourly => "Last hour",
+                DigestInterval::Daily => "Last 24h",
+            },
+            lines.join("\n")
+        ))
+    }
+
+    // The digest message once the current interval has passed, starting
+    // a new one. None while the interval lasts or if nothing happened.
+    pub fn take_due(&self) -> Option<String> {
+        let mut state = self.state.lock().unwrap();
+
+        if now_secs() < state.period_start + interval_secs(self.config.interval) {
+            return None;
+        }
+
+        let msg = self.render(&state);
+
+        *state = DigestState {
+            period_start: Digest::current_period_start(self.config.interval),
+            sources: BTreeMap::new(),
+        };
+
+        self.save(&state);
+
+        msg
+    }
+}
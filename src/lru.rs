@@ -0,0 +1,118 @@
+/*
+ * LEAST-RECENTLY-USED MAP
+ *
+ * Bounded map that evicts the entry used least recently once full,
+ * for state that would otherwise grow for as long as the bot runs.
+ */
+
+use std::{
+    borrow::Borrow,
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+};
+
+pub struct Lru<K, V> {
+    capacity: usize,
+    entries: HashMap<K, V>,
+    order: VecDeque<K>,
+}
+
+impl<K: Clone + Eq + Hash, V: Clone> Lru<K, V> {
+    pub fn new(capacity: usize) -> Lru<K, V> {
+        Lru {
+            capacity,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn touch<Q>(&mut self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if let Some(pos) = self.order.iter().position(|entry| entry.borrow() == key) {
+            if let Some(entry) = self.order.remove(pos) {
+                self.order.push_back(entry);
+            }
+        }
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let value = self.entries.get(key).cloned()?;
+
+        self.touch(key);
+
+        Some(value)
+    }
+
+    pub fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.entries.insert(key.clone(), value).is_some() {
+            self.touch(&key);
+        } else {
+            self.order.push_back(key);
+        }
+
+        while self.order.len() > self.capacity {
+            if let Some(evicted) = self.order.pop_front() {
+                self.entries.remove(&evicted);
+            }
+        }
+    }
+
+    // Entries in no particular order, without counting as a use
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.entries.values_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut lru = Lru::new(2);
+
+        lru.insert("a".to_string(), 1);
+        lru.insert("b".to_string(), 2);
+
+        assert_eq!(lru.get("a"), Some(1));
+
+        lru.insert("c".to_string(), 3);
+
+        assert_eq!(lru.get("b"), None);
+        assert_eq!(lru.get("a"), Some(1));
+        assert_eq!(lru.get("c"), Some(3));
+    }
+
+    #[test]
+    fn replaces_values() {
+        let mut lru = Lru::new(2);
+
+        lru.insert(1, "a");
+        lru.insert(2, "b");
+        lru.insert(1, "c");
+        lru.insert(3, "d");
+
+        assert_eq!(lru.get(&1), Some("c"));
+        assert_eq!(lru.get(&2), None);
+    }
+
+    #[test]
+    fn stores_nothing_without_capacity() {
+        let mut lru = Lru::new(0);
+
+        lru.insert(1, 1);
+
+        assert_eq!(lru.get(&1), None);
+    }
+}
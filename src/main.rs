@@ -16,21 +16,50 @@ extern crate telegram_bot;
 extern crate tokio_core;
 extern crate url;
 
+mod anon;
+mod approvals;
+mod bot;
+mod config;
+mod control;
+mod digest;
+mod lru;
+mod photo;
+mod risk;
+mod suppression;
+mod threads;
+mod watchlist;
+mod wiki_api;
+mod wikitext;
+
 use std::{
+    collections::HashMap,
+    fs,
     io::Read,
-    net::UdpSocket,
+    net::{SocketAddr, UdpSocket},
+    sync::{mpsc, Arc},
+    thread,
+    time::{Duration, Instant},
 };
 
-use afterparty::{Delivery, Hub};
+use afterparty::Delivery;
 use hyper::{Client, Server};
 use hyper::net::HttpsConnector;
 use hyper_native_tls::NativeTlsClient;
 use regex::Regex;
 use scoped_threadpool::Pool;
 use telegram_bot::prelude::*;
-use url::percent_encoding::{
-    percent_decode, percent_encode, QUERY_ENCODE_SET,
-};
+use url::percent_encoding::percent_decode;
+
+use anon::{AnonEditors, Editor, FloodDecision};
+use bot::Bot;
+use config::{Config, GithubConfig, NamespaceConfig, ThreadingMode, Watcher, WikiConfig};
+use control::{now_secs, Control, Source};
+use photo::{SendPhoto, MAX_CAPTION_LENGTH};
+use risk::{RiskLevel, RiskScorer};
+use suppression::Suppressor;
+use threads::Thread;
+use wikitext::WikitextRenderer;
+use wiki_api::{DiffLine, ImageInfo, RevInfo, WikiAction, WikiApiClient};
 
 
 const MEDIAWIKI_ENDPOINT: &'static str = "0.0.0.0:3000";
@@ -38,7 +67,26 @@ const GITHUB_ENDPOINT: &'static str = "0.0.0.0:4567";
 const JIRA_ENDPOINT: &'static str = "0.0.0.0:9293";
 const PAYPAL_ENDPOINT: &'static str = "0.0.0.0:9728";
 
-const PW_API_URL_PREFIX: &'static str = "https://psychonautwiki.org/w/api.php";
+// Pull-request actions only announced if configured
+const GITHUB_NOISY_PR_ACTIONS: &[&str] = &[
+    "labeled",
+    "unlabeled",
+    "assigned",
+    "unassigned",
+    "review_requested",
+    "review_request_removed",
+    // pushes to the branch of the pull-request
+    "synchronize",
+];
+
+// characters of a message, after parsing its markup
+const MAX_MESSAGE_LENGTH: usize = 4096;
+
+// upper bound of events handled per batch
+const MEDIAWIKI_MAX_BATCH: usize = 200;
+
+// housekeeping interval while no events arrive
+const MEDIAWIKI_IDLE_TICK: Duration = Duration::from_secs(60);
 
 // SANDBOX
 //const PAYPAL_IPN_VERIFY_URL: &'static str = "https://ipnpb.sandbox.paypal.com/cgi-bin/webscr?cmd=_notify-validate&";
@@ -76,126 +124,15 @@ fn verify_paypal_ipn(ipn_payload: impl Into<String>) -> bool {
     &buf == b"VERIFIED"
 }
 
-fn legacy_hyper_load_url(url: String) -> Option<json::JsonValue> {
-    let client = Client::new();
-
-    let res = client.get(&url).send();
-
-    if !res.is_ok() {
-        return None;
-    }
-
-    let mut res = res.unwrap();
-
-    let mut buf = String::new();
-
-    match res.read_to_string(&mut buf) {
-        Ok(_) => {}
-        _ => {
-            return None;
-        }
-    }
-
-    match json::parse(&buf) {
-        Ok(data) => Some(data),
-        Err(_) => None
-    }
-}
-
-#[derive(Debug)]
-struct RevInfo(String, String, String);
-
-fn get_revision_info(title: String, rev_id: String) -> Option<RevInfo> {
-    let title = title;
-    let rev_id = rev_id;
-
-    let url = format!(
-        "{}?action=query&prop=revisions&titles={}&rvprop=timestamp%7Cuser%7Ccomment%7Ccontent%7Cids&rvstartid={}&rvendid={}&format=json",
-        PW_API_URL_PREFIX,
-        title,
-        rev_id,
-        rev_id
-    );
-
-    let revision_data = legacy_hyper_load_url(url);
-
-    if !revision_data.is_some() {
-        return None;
-    }
-
-    let revision_data = revision_data.unwrap();
-
-    let pages = &revision_data["query"]["pages"];
-
-    // "-1" is used to denote "not found" in mediawiki
-    if pages.has_key("-1") || pages.len() != 1usize {
-        return None;
-    }
-
-    // Obtain page_id
-
-    let mut entry: &str = "";
-
-    for (key, _) in pages.entries() {
-        entry = key;
-    }
-
-    // try to obtain the target revision
-    let results = &pages[entry]["revisions"][0].clone();
-
-    if results.is_empty() {
-        return None;
-    }
-
-    Some(
-        RevInfo(
-            results["user"].to_string(),
-            results["comment"].to_string(),
-            results["parentid"].to_string(),
-        )
-    )
-}
-
-struct EmitterRgx {
-    percent_rgx: regex::Regex,
-    plus_rgx: regex::Regex,
-    and_rgx: regex::Regex,
-    questionmark_rgx: regex::Regex,
-}
-
-impl EmitterRgx {
-    fn new() -> EmitterRgx {
-        let percent_rgx = Regex::new(r"%").unwrap();
-        let plus_rgx = Regex::new(r"\+").unwrap();
-        let and_rgx = Regex::new(r"\&").unwrap();
-        let questionmark_rgx = Regex::new(r"\?").unwrap();
-
-        EmitterRgx {
-            percent_rgx,
-            plus_rgx,
-            and_rgx,
-            questionmark_rgx,
-        }
-    }
-
-    fn percent_to_url(&self, orig: &str) -> String {
-        self.percent_rgx.replace_all(orig, "%25").to_string()
-    }
-
-    fn plusexclquest_to_url(&self, orig: &str) -> String {
-        let orig_pr = self.plus_rgx.replace_all(orig, "%2b").to_string();
-        let orig_ar = self.and_rgx.replace_all(&orig_pr, "%26").to_string();
-
-        self.questionmark_rgx.replace_all(&orig_ar, "%3f").to_string()
-    }
-}
-
 struct ConfiguredApi {
     api: telegram_bot::Api,
     core: std::cell::RefCell<tokio_core::reactor::Core>,
     channel_id: i64,
     name: String,
     parse_mode: telegram_bot::types::ParseMode,
+    control: Arc<Control>,
+    // event source messages are accounted to, and muted by
+    source: Option<Source>,
 }
 
 fn htmlescape_str<T: Into<String>>(msg: T) -> String {
@@ -216,8 +153,24 @@ fn htmlescape_str<T: Into<String>>(msg: T) -> String {
     }
 }
 
+// Written next to the state file first, so a crash while saving
+// can't leave a truncated one behind
+fn save_state<T: serde::Serialize + ?Sized>(path: &str, state: &T) -> Result<(), String> {
+    let tmp_path = format!("{}.tmp", path);
+
+    serde_json::to_string(state)
+        .map_err(|err| err.to_string())
+        .and_then(|raw| fs::write(&tmp_path, raw).map_err(|err| err.to_string()))
+        .and_then(|_| fs::rename(&tmp_path, path).map_err(|err| err.to_string()))
+}
+
 impl ConfiguredApi {
-    fn new(name: &str, parse_mode: telegram_bot::types::ParseMode) -> ConfiguredApi {
+    fn new(
+        name: &str,
+        parse_mode: telegram_bot::types::ParseMode,
+        control: Arc<Control>,
+        source: Option<Source>,
+    ) -> ConfiguredApi {
         let core = tokio_core::reactor::Core::new().unwrap();
 
         let token = std::env::var("TELEGRAM_TOKEN").unwrap();
@@ -230,13 +183,176 @@ impl ConfiguredApi {
             channel_id: -1001050593583,
             name: name.to_string(),
             parse_mode,
+            control,
+            source,
         }
     }
 
     fn emit<T: Into<String>>(&self, msg: T, should_notify: bool) {
+        self.emit_to(self.channel_id, msg, should_notify);
+    }
+
+    // Count the event towards the periodic digest instead of posting it,
+    // if its stream is digested
+    fn digest(&self, kind: &str, user: &str, subject: &str) -> bool {
+        match self.source {
+            Some(source) => self.control.digest().record(source, kind, user, subject),
+            None => false,
+        }
+    }
+
+    // Post to the channel in the thread of the given entity
+    fn emit_threaded<T: Into<String>>(&self, thread_key: &str, msg: T, should_notify: bool) {
+        self.emit_in_thread(Some(thread_key), self.channel_id, msg, should_notify, None);
+    }
+
+    fn emit_to<T: Into<String>>(&self, channel_id: i64, msg: T, should_notify: bool) {
+        self.emit_with_markup(channel_id, msg, should_notify, None);
+    }
+
+    fn emit_with_markup<T: Into<String>>(
+        &self,
+        channel_id: i64,
+        msg: T,
+        should_notify: bool,
+        reply_markup: Option<telegram_bot::InlineKeyboardMarkup>,
+    ) {
+        self.emit_in_thread(None, channel_id, msg, should_notify, reply_markup);
+    }
+
+    // Post a message about the entity identified by `thread_key`. Depending
+    // on the threading mode of the source, messages following the first one
+    // about an entity reply to it or are appended to it.
+    fn emit_in_thread<T: Into<String>>(
+        &self,
+        thread_key: Option<&str>,
+        channel_id: i64,
+        msg: T,
+        should_notify: bool,
+        reply_markup: Option<telegram_bot::InlineKeyboardMarkup>,
+    ) {
+        self.emit_photo_in_thread(thread_key, channel_id, None, msg, should_notify, reply_markup);
+    }
+
+    // Like `emit_in_thread`, with the message as caption of a photo given by
+    // url, if any. Only the caption is appended to messages in edit mode, and
+    // it's posted as a message of its own if the photo can't be sent.
+    fn emit_photo_in_thread<T: Into<String>>(
+        &self,
+        thread_key: Option<&str>,
+        channel_id: i64,
+        photo_url: Option<&str>,
+        msg: T,
+        should_notify: bool,
+        reply_markup: Option<telegram_bot::InlineKeyboardMarkup>,
+    ) {
+        let threading = match self.source {
+            Some(source) => {
+                if !self.control.record(source) {
+                    return;
+                }
+
+                self.control.threading(source)
+            }
+            None => ThreadingMode::Off,
+        };
+
+        let thread_key = thread_key.filter(|_| threading != ThreadingMode::Off);
+
+        let thread = thread_key.and_then(|key| self.control.thread(key));
+
         let msg = format!("⥂ {} ⟹ {}", self.name, msg.into());
 
-        let channel = telegram_bot::ChannelId::new(self.channel_id);
+        if let (ThreadingMode::Edit, Some(key), Some(thread)) = (threading, thread_key, thread.clone()) {
+            // follow-ups are appended, and keep the buttons unless they bring their own
+            let text = format!("{}\n\n{}", thread.text, msg);
+            let reply_markup = reply_markup.clone().or(thread.reply_markup);
+
+            // the original message may have been deleted meanwhile or be
+            // full, post a new one in those cases
+            if text.chars().count() <= MAX_MESSAGE_LENGTH
+                && self.edit(thread.chat_id, thread.message_id, text.clone(), reply_markup.clone())
+            {
+                self.control.remember_thread(key, Thread { text, reply_markup, ..thread });
+
+                return;
+            }
+        }
+
+        let reply_to = match (threading, thread) {
+            (ThreadingMode::Reply, Some(thread)) if thread.chat_id == channel_id => Some(thread.message_id),
+            _ => None
+        };
+
+        // photos are sent without buttons
+        let photo_message_id = match (photo_url, reply_markup.is_none()) {
+            (Some(photo_url), true) => self.send_photo(channel_id, photo_url, &msg, should_notify, reply_to),
+            _ => None,
+        };
+
+        if photo_message_id.is_some() {
+            return;
+        }
+
+        let message_id = self.send(channel_id, msg.clone(), should_notify, reply_markup.clone(), reply_to);
+
+        // replies keep referring to the top-level message
+        if let (Some(key), Some(message_id), None) = (thread_key, message_id, reply_to) {
+            self.control.remember_thread(key, Thread {
+                chat_id: channel_id,
+                message_id,
+                text: msg,
+                reply_markup,
+            });
+        }
+    }
+
+    // Post a photo by url, None if it couldn't be sent
+    fn send_photo(
+        &self,
+        channel_id: i64,
+        photo_url: &str,
+        caption: &str,
+        should_notify: bool,
+        reply_to: Option<i64>,
+    ) -> Option<i64> {
+        if caption.chars().count() > MAX_CAPTION_LENGTH {
+            return None;
+        }
+
+        let mut send_photo = SendPhoto::new(telegram_bot::ChannelId::new(channel_id), photo_url, caption);
+
+        send_photo.parse_mode(self.parse_mode);
+
+        if !should_notify {
+            send_photo.disable_notification();
+        }
+
+        if let Some(reply_to) = reply_to {
+            send_photo.reply_to(telegram_bot::MessageId::new(reply_to));
+        }
+
+        let tg_future = self.api.send(send_photo);
+
+        match self.core.borrow_mut().run(tg_future) {
+            Ok(message) => Some(i64::from(message.id)),
+            Err(err) => {
+                println!("couldn't send photo {}: {:?}", photo_url, err);
+
+                None
+            }
+        }
+    }
+
+    fn send(
+        &self,
+        channel_id: i64,
+        msg: String,
+        should_notify: bool,
+        reply_markup: Option<telegram_bot::InlineKeyboardMarkup>,
+        reply_to: Option<i64>,
+    ) -> Option<i64> {
+        let channel = telegram_bot::ChannelId::new(channel_id);
 
         let mut chan_msg = channel.text(msg);
 
@@ -244,6 +360,14 @@ impl ConfiguredApi {
             .parse_mode(self.parse_mode)
             .disable_preview();
 
+        if let Some(reply_markup) = reply_markup {
+            msg_op.reply_markup(reply_markup);
+        }
+
+        if let Some(reply_to) = reply_to {
+            msg_op.reply_to(telegram_bot::MessageId::new(reply_to));
+        }
+
         let msg_op_notif = match should_notify {
             true => msg_op,
             false => msg_op.disable_notification()
@@ -253,7 +377,43 @@ impl ConfiguredApi {
             msg_op_notif
         );
 
-        let _ = self.core.borrow_mut().run(tg_future);
+        self.core.borrow_mut()
+            .run(tg_future)
+            .ok()
+            .map(|message| i64::from(message.id))
+    }
+
+    fn edit(
+        &self,
+        channel_id: i64,
+        message_id: i64,
+        msg: String,
+        reply_markup: Option<telegram_bot::InlineKeyboardMarkup>,
+    ) -> bool {
+        let mut edit_msg = telegram_bot::EditMessageText::new(
+            telegram_bot::ChannelId::new(channel_id),
+            telegram_bot::MessageId::new(message_id),
+            msg,
+        );
+
+        let edit_op = edit_msg
+            .parse_mode(self.parse_mode)
+            .disable_preview();
+
+        if let Some(reply_markup) = reply_markup {
+            edit_op.reply_markup(reply_markup);
+        }
+
+        let tg_future = self.api.send(edit_op);
+
+        match self.core.borrow_mut().run(tg_future) {
+            Ok(_) => true,
+            Err(err) => {
+                println!("couldn't edit message {} in {}: {:?}", message_id, channel_id, err);
+
+                false
+            }
+        }
     }
 }
 
@@ -263,28 +423,386 @@ impl ConfiguredApi {
 
 struct MediaWikiEmitter {
     configured_api: ConfiguredApi,
-    emitter_rgx: EmitterRgx,
+    wiki_api: WikiApiClient,
+    // revisions looked up for the batch being handled
+    batch_revisions: std::cell::RefCell<HashMap<u32, RevInfo>>,
+    wiki: WikiConfig,
+    // position of the wiki in the configuration, identifies it in buttons
+    wiki_idx: usize,
+    categorize_rgx: Regex,
+    categorize_enabled: bool,
+    categorize_channel_id: Option<i64>,
+    large_removal_percent: u32,
+    diff_excerpts: bool,
+    diff_excerpt_lines: usize,
+    diff_excerpt_line_length: usize,
+    namespaces: NamespaceConfig,
+    suppressor: Suppressor,
+    risk_scorer: RiskScorer,
+    moderators_channel_id: Option<i64>,
+    actions_enabled: bool,
+    anon_editors: AnonEditors,
+    wikitext: WikitextRenderer,
+    upload_previews: bool,
+    upload_preview_width: u32,
 }
 
 impl MediaWikiEmitter {
-    fn new() -> MediaWikiEmitter {
-        let configured_api = ConfiguredApi::new(&"<b>MediaWiki</b>", telegram_bot::types::ParseMode::Html);
+    fn new(config: &Config, wiki: &WikiConfig, wiki_idx: usize, control: Arc<Control>) -> MediaWikiEmitter {
+        let name = match wiki.label.is_empty() {
+            true => "<b>MediaWiki</b>".to_string(),
+            false => format!("<b>MediaWiki ({})</b>", htmlescape_str(wiki.label.as_str())),
+        };
+
+        let configured_api = ConfiguredApi::new(
+            &name,
+            telegram_bot::types::ParseMode::Html,
+            control.clone(),
+            Some(Source::MediaWiki),
+        );
 
-        let emitter_rgx = EmitterRgx::new();
+        let wiki_api = WikiApiClient::new(&config.mediawiki, wiki);
+
+        // "[[:Page]] added to category" / "[[:Page]] removed from category",
+        // possibly followed by a note on further pages using the page
+        let categorize_rgx = Regex::new(
+            r"^\[\[:?([^\]|]+)(?:\|[^\]]*)?\]\] (added to|removed from) category"
+        ).unwrap();
 
         MediaWikiEmitter {
             configured_api,
-            emitter_rgx,
+            wiki_api,
+            batch_revisions: std::cell::RefCell::new(HashMap::new()),
+            wiki: wiki.clone(),
+            wiki_idx,
+            categorize_rgx,
+            categorize_enabled: config.mediawiki.categorize_enabled,
+            categorize_channel_id: config.mediawiki.categorize_channel_id,
+            large_removal_percent: config.mediawiki.large_removal_percent,
+            diff_excerpts: config.mediawiki.diff_excerpts,
+            diff_excerpt_lines: config.mediawiki.diff_excerpt_lines,
+            diff_excerpt_line_length: config.mediawiki.diff_excerpt_line_length,
+            namespaces: config.mediawiki.namespaces.clone(),
+            suppressor: Suppressor::new(&config.mediawiki.suppression, control.clone()),
+            risk_scorer: RiskScorer::new(
+                &config.mediawiki.risk,
+                config.mediawiki.large_removal_percent,
+                &config.mediawiki.anon.temp_account_prefix,
+            ),
+            moderators_channel_id: config.mediawiki.risk.moderators_channel_id,
+            actions_enabled: config.mediawiki.actions.enabled,
+            anon_editors: AnonEditors::new(&config.mediawiki.anon),
+            wikitext: WikitextRenderer::new(&wiki.article_url),
+            upload_previews: config.mediawiki.upload_previews,
+            upload_preview_width: config.mediawiki.upload_preview_width,
+        }
+    }
+
+    // Revisions whose metadata the given event will look up when rendered.
+    fn lookup_rev_ids(evt: &json::JsonValue) -> Vec<u32> {
+        if evt["type"] != "log" {
+            return Vec::new();
+        }
+
+        let log_params = &evt["log_params"];
+
+        match (evt["log_type"].as_str(), evt["log_action"].as_str()) {
+            (Some("patrol"), _) if log_params["auto"].as_u32() == Some(0) =>
+                log_params["curid"].as_u32().into_iter().collect(),
+            (Some("approval"), Some("approve")) =>
+                MediaWikiEmitter::approval_rev_ids(evt).0.into_iter().collect(),
+            (Some("approval"), Some("unapprove")) =>
+                MediaWikiEmitter::unapproval_rev_id(evt).into_iter().collect(),
+            _ => Vec::new()
+        }
+    }
+
+    // Whether an event received on the given port from the given
+    // address originates from this emitter's wiki
+    fn accepts(&self, evt: &json::JsonValue, port: u16, source: &SocketAddr) -> bool {
+        let matches_field = |expected: &Option<String>, field: &str| match expected {
+            Some(expected) => evt[field].as_str() == Some(expected.as_str()),
+            None => true,
+        };
+
+        self.wiki.port.map_or(true, |wiki_port| wiki_port == port)
+            && self.wiki.source_address.map_or(true, |address| address == source.ip())
+            && matches_field(&self.wiki.server_name, "server_name")
+            && matches_field(&self.wiki.wiki, "wiki")
+    }
+
+    // Handle a burst of events, resolving all revision lookups they
+    // need with as few API requests as possible beforehand.
+    fn handle_batch(&self, evts: &[json::JsonValue]) {
+        let rev_ids = evts
+            .iter()
+            .flat_map(MediaWikiEmitter::lookup_rev_ids)
+            .collect::<Vec<u32>>();
+
+        *self.batch_revisions.borrow_mut() = self.wiki_api.prefetch_revisions(&rev_ids);
+
+        for evt in evts {
+            self.handle_evt(evt);
+        }
+
+        self.batch_revisions.borrow_mut().clear();
+
+        self.report_calmed_floods();
+    }
+
+    // Revision metadata, as prefetched for the current batch
+    fn get_revision_info(&self, rev_id: u32) -> Option<RevInfo> {
+        if let Some(rev_info) = self.batch_revisions.borrow().get(&rev_id) {
+            return Some(rev_info.clone());
+        }
+
+        self.wiki_api.get_revision_info(rev_id)
+    }
+
+    fn is_namespace_included(&self, evt: &json::JsonValue) -> bool {
+        if evt["type"] == "log" && !self.namespaces.filter_logs {
+            return true;
+        }
+
+        let namespace = match evt["namespace"].as_i64() {
+            Some(namespace) => namespace,
+            None => return true
+        };
+
+        if self.namespaces.exclude.contains(&namespace) {
+            return false;
+        }
+
+        self.namespaces.include.is_empty() || self.namespaces.include.contains(&namespace)
+    }
+
+    // Titles an event concerns, as far as watchers are notified about it
+    fn watched_titles(evt: &json::JsonValue) -> Vec<String> {
+        let title = evt["title"].to_string();
+
+        match (evt["type"].as_str(), evt["log_type"].as_str()) {
+            (Some("edit"), _) | (Some("new"), _) => vec![title],
+            (Some("log"), Some("move")) => vec![title, evt["log_params"]["target"].to_string()],
+            (Some("log"), Some("delete" | "approval" | "upload")) => vec![title],
+            _ => Vec::new()
+        }
+    }
+
+    // Stream of an event as named in the digest configuration
+    fn digest_kind(evt: &json::JsonValue) -> Option<String> {
+        let kind = match evt["type"].as_str()? {
+            "edit" if evt["bot"] == true => "bot_edit".to_string(),
+            "edit" if evt["minor"] == true => "minor_edit".to_string(),
+            "log" => format!("log/{}", evt["log_type"]),
+            evt_type => evt_type.to_string(),
+        };
+
+        Some(kind)
+    }
+
+    // Count the event towards the periodic digest instead of posting it,
+    // if its stream is digested
+    fn digest(&self, evt: &json::JsonValue) -> bool {
+        let kind = match MediaWikiEmitter::digest_kind(evt) {
+            Some(kind) => kind,
+            None => return false,
+        };
+
+        let title = match self.wiki.label.is_empty() {
+            true => evt["title"].to_string(),
+            false => format!("{} ({})", evt["title"], self.wiki.label),
+        };
+
+        self.configured_api.digest(&kind, &evt["user"].to_string(), &title)
+    }
+
+    // Watchers of the pages an event concerns
+    fn watchers(&self, evt: &json::JsonValue) -> Vec<Watcher> {
+        let titles = MediaWikiEmitter::watched_titles(evt);
+
+        if titles.is_empty() {
+            return Vec::new();
+        }
+
+        let watchlist = self.configured_api.control.watchlist();
+
+        let categories = match watchlist.has_category_watches() {
+            true => self.wiki_api.get_page_categories(&titles[0]),
+            false => Vec::new()
+        };
+
+        watchlist.watchers_of(&titles, &categories)
+    }
+
+    // Revision an event concerns, for threading the messages about it
+    fn thread_key(&self, evt: &json::JsonValue) -> Option<String> {
+        let rev_id = match (evt["type"].as_str(), evt["log_type"].as_str()) {
+            (Some("edit"), _) | (Some("new"), _) => evt["revision"]["new"].as_u32(),
+            (Some("log"), Some("patrol")) => evt["log_params"]["curid"].as_u32(),
+            (Some("log"), Some("approval")) => MediaWikiEmitter::approval_rev_ids(evt).0,
+            _ => None
+        };
+
+        rev_id.map(|rev_id| format!("mediawiki:{}:rev:{}", self.wiki_idx, rev_id))
+    }
+
+    // Chat and notification setting for a message about the given
+    // event, as configured for its namespace and suppression rules
+    fn route(&self, evt: &json::JsonValue, should_notify: bool) -> (i64, bool) {
+        let route = evt["namespace"]
+            .as_i64()
+            .and_then(|namespace| self.namespaces.routes.get(&namespace));
+
+        let (channel_id, should_notify) = match route {
+            Some(route) => (
+                route.channel_id.unwrap_or(self.configured_api.channel_id),
+                route.notify.unwrap_or(should_notify),
+            ),
+            None => (self.configured_api.channel_id, should_notify)
+        };
+
+        // category membership changes may have a chat of their own
+        let channel_id = match self.categorize_channel_id {
+            Some(categorize_channel_id) if evt["type"] == "categorize" => categorize_channel_id,
+            _ => channel_id
+        };
+
+        (channel_id, should_notify && !self.suppressor.should_silence(evt))
+    }
+
+    // Post a message about the given event, applying the chat and
+    // notification settings configured for its namespace. Watchers
+    // of the affected pages are mentioned or messaged directly.
+    fn emit(&self, evt: &json::JsonValue, msg: String, should_notify: bool) {
+        self.emit_with_markup(evt, msg, should_notify, None);
+    }
+
+    fn emit_with_markup(
+        &self,
+        evt: &json::JsonValue,
+        msg: String,
+        should_notify: bool,
+        reply_markup: Option<telegram_bot::InlineKeyboardMarkup>,
+    ) {
+        self.emit_or_digest(evt, self.watchers(evt), msg, None, should_notify, reply_markup);
+    }
+
+    // Events of digested streams only count towards the digest, unless
+    // one of the `watchers` of the event is to be notified
+    fn emit_or_digest(
+        &self,
+        evt: &json::JsonValue,
+        watchers: Vec<Watcher>,
+        msg: String,
+        photo_url: Option<&str>,
+        should_notify: bool,
+        reply_markup: Option<telegram_bot::InlineKeyboardMarkup>,
+    ) {
+        if watchers.is_empty() && self.digest(evt) {
+            return;
+        }
+
+        self.post(evt, watchers, msg, photo_url, should_notify, reply_markup);
+    }
+
+    // Post a message about the given event, mentioning or messaging its
+    // `watchers`. The message becomes the caption of the photo at
+    // `photo_url`, if given.
+    fn post(
+        &self,
+        evt: &json::JsonValue,
+        watchers: Vec<Watcher>,
+        msg: String,
+        photo_url: Option<&str>,
+        should_notify: bool,
+        reply_markup: Option<telegram_bot::InlineKeyboardMarkup>,
+    ) {
+        let mentions = watchers
+            .iter()
+            .filter(|watcher| !watcher.dm)
+            .map(|watcher| watcher.mention())
+            .collect::<Vec<String>>();
+
+        let (channel_id, should_notify) = self.route(evt, should_notify);
+
+        for watcher in watchers.iter().filter(|watcher| watcher.dm) {
+            self.configured_api.emit_photo_in_thread(None, watcher.telegram_user_id, photo_url, msg.clone(), true, None);
+        }
+
+        let thread_key = self.thread_key(evt);
+
+        if mentions.is_empty() {
+            self.configured_api.emit_photo_in_thread(
+                thread_key.as_deref(),
+                channel_id,
+                photo_url,
+                msg,
+                should_notify,
+                reply_markup,
+            );
+        } else {
+            self.configured_api.emit_photo_in_thread(
+                thread_key.as_deref(),
+                channel_id,
+                photo_url,
+                format!("{}\n👁 {}", msg, mentions.join(", ")),
+                true,
+                reply_markup,
+            );
+        }
+    }
+
+    // Follow edits and approvals of pages needing approval, regardless
+    // of whether they are posted
+    fn track_approval(&self, evt: &json::JsonValue) {
+        let approvals = self.configured_api.control.approvals();
+
+        if !approvals.is_tracked_namespace(evt["namespace"].as_i64().unwrap_or(-1)) {
+            return;
+        }
+
+        let title = evt["title"].to_string();
+        let timestamp = evt["timestamp"].as_i64().unwrap_or(0);
+
+        match (evt["type"].as_str(), evt["log_action"].as_str()) {
+            (Some("edit"), _) | (Some("new"), _) => {
+                if let Some(new_rev) = evt["revision"]["new"].as_u32() {
+                    approvals.note_edit(self.wiki_idx, &title, evt["revision"]["old"].as_u32(), new_rev, timestamp);
+                }
+            }
+            (Some("log"), Some("approve")) if evt["log_type"] == "approval" => {
+                approvals.note_approval(self.wiki_idx, &title, MediaWikiEmitter::approval_rev_ids(evt).0);
+            }
+            (Some("log"), Some("unapprove")) if evt["log_type"] == "approval" => {
+                let formerly_approved_rev = MediaWikiEmitter::unapproval_rev_id(evt);
+
+                approvals.note_unapproval(self.wiki_idx, &title, formerly_approved_rev, timestamp);
+            }
+            (Some("log"), Some("delete")) if evt["log_type"] == "delete" => {
+                approvals.note_deletion(self.wiki_idx, &title);
+            }
+            (Some("log"), Some("move" | "move_redir")) if evt["log_type"] == "move" => {
+                if let Some(target) = evt["log_params"]["target"].as_str() {
+                    approvals.note_move(self.wiki_idx, &title, target);
+                }
+            }
+            _ => ()
         }
     }
 
     fn handle_evt(&self, evt: &json::JsonValue) {
         let evt_type = evt["type"].to_string();
 
-        dbg!(&evt.dump());
+        self.track_approval(evt);
+
+        if !self.is_namespace_included(evt) || self.suppressor.should_drop(evt) {
+            return;
+        }
 
         match &*evt_type {
+            "categorize" => self.handle_evt_categorize(evt),
             "edit" => self.handle_evt_edit(evt),
+            "external" => self.handle_evt_external(evt),
             "log" => self.handle_evt_log(evt),
             "new" => self.handle_evt_new(evt),
             _ => {
@@ -297,46 +815,113 @@ impl MediaWikiEmitter {
                     evt.dump()
                 );
 
-                self.configured_api.emit(msg, true);
+                self.emit(evt, msg, true);
             }
         }
     }
 
-    fn urlencode(orig: &str) -> String {
-        percent_encode(orig.as_bytes(), QUERY_ENCODE_SET).collect::<String>()
-    }
-
     fn urldecode(orig: &str) -> String {
         String::from_utf8(
             percent_decode(orig.as_bytes()).collect::<Vec<u8>>()
         ).unwrap_or("<string conversion failed>".to_string())
     }
 
-    // do an additional encode on top of urlencode
-    // as the url crate doesn't allow for double-encode
-    // as per ISO specification
-    fn wrap_urlencode(&self, orig: &str) -> String {
-        self.emitter_rgx.percent_to_url(orig)
+    fn emit_flood_resumed(&self, range: &str, held: usize) {
+        self.configured_api.emit(format!(
+            r#"[flood] <a href="{}">{}</a> calmed down, {} edits were held back"#,
+            self.get_contributions_url(range),
+            range,
+            held
+        ), false);
     }
 
+    // Summarize the floods of ranges that went quiet since
+    fn report_calmed_floods(&self) {
+        for (range, held) in self.anon_editors.take_calmed(now_secs() as i64) {
+            self.emit_flood_resumed(&range, held);
+        }
+    }
+
+    // Holds back edits of IP ranges flooding the wiki, returns false
+    // if the event shouldn't be posted.
+    fn check_flood(&self, evt: &json::JsonValue) -> bool {
+        let ip = match self.anon_editors.classify(&evt["user"].to_string()) {
+            Editor::Ip(ip) => ip,
+            _ => return true,
+        };
+
+        match self.anon_editors.note_edit(ip, evt["timestamp"].as_i64().unwrap_or(0)) {
+            FloodDecision::Post => true,
+            FloodDecision::Resumed { range, held } => {
+                self.emit_flood_resumed(&range, held);
+
+                true
+            }
+            FloodDecision::Alert { range, edits } => {
+                self.configured_api.emit(format!(
+                    r#"[flood] <b>{}</b> edits from <a href="{}">{}</a> in a short time, holding back further edits from this range"#,
+                    edits,
+                    self.get_contributions_url(&range),
+                    range
+                ), true);
+
+                false
+            }
+            FloodDecision::Hold => false,
+        }
+    }
+
+    // IP addresses and temporary accounts have no user page
     fn get_user_url(&self, user: &str) -> String {
-        let target = format!(
-            "User:{}",
-            user
-        );
+        match self.anon_editors.classify(user) {
+            Editor::Registered => self.get_url(&format!("User:{}", user)),
+            Editor::Ip(_) | Editor::Temporary => self.get_contributions_url(user),
+        }
+    }
 
-        self.get_url(&target)
+    // Also accepts IP ranges, e.g. "203.0.113.0/24"
+    fn get_contributions_url(&self, target: &str) -> String {
+        self.get_url(&format!("Special:Contributions/{}", target))
+    }
+
+    // e.g. " (IP, DE)" for IP addresses
+    fn explain_editor(&self, user: &str) -> String {
+        match self.anon_editors.classify(user) {
+            Editor::Registered => String::new(),
+            Editor::Temporary => " (temporary account)".to_string(),
+            Editor::Ip(ip) => match self.anon_editors.country(ip) {
+                Some(country) => format!(" (IP, {})", country),
+                None => " (IP)".to_string(),
+            },
+        }
     }
 
     fn get_url(&self, page: &str) -> String {
         let url = format!(
-            "https://psychonautwiki.org/wiki/{}",
-            page
+            "{}/{}",
+            self.wiki.article_url,
+            wiki_api::encode_title_path(page)
         );
 
         url
     }
 
+    fn get_index_url(&self, params: &[(&str, &str)]) -> String {
+        format!(
+            "{}?{}",
+            self.wiki.index_url,
+            wiki_api::encode_query(params)
+        )
+    }
+
+    // A log parameter, escaped for HTML, None if it is absent
+    fn log_param(log_params: &json::JsonValue, name: &str) -> Option<String> {
+        match log_params[name] {
+            json::JsonValue::Null => None,
+            ref value => Some(htmlescape_str(value.to_string())),
+        }
+    }
+
     fn cond_string(cond: bool, protagonist: &str, antagonist: &str) -> String {
         match cond {
             true => protagonist.to_string(),
@@ -344,12 +929,279 @@ impl MediaWikiEmitter {
         }
     }
 
-    fn explain_comment(comment: &str) -> String {
+    // Summary of a change to the given page, wikitext rendered
+    fn explain_comment(&self, comment: &str, page: &str) -> String {
         if comment == "" {
             return format!("without summary");
         }
 
-        return format!("with summary: {}", comment);
+        return format!("with summary: {}", self.wikitext.render(comment, page));
+    }
+
+    // 1234567 -> "1,234,567"
+    fn group_digits(num: i64) -> String {
+        let digits = num.abs().to_string();
+
+        let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                grouped.push(',');
+            }
+
+            grouped.push(digit);
+        }
+
+        grouped
+    }
+
+    // Size change of an edit, e.g. "(+1,234 bytes)" or "(−56 bytes)".
+    // Removals above the configured share of the page are highlighted.
+    fn explain_size_delta(&self, evt: &json::JsonValue) -> String {
+        let (len_old, len_new) = match (evt["length"]["old"].as_i64(), evt["length"]["new"].as_i64()) {
+            (Some(len_old), Some(len_new)) => (len_old, len_new),
+            _ => return String::new()
+        };
+
+        let delta = len_new - len_old;
+
+        let delta_str = format!(
+            "{}{} bytes",
+            MediaWikiEmitter::cond_string(delta < 0, "−", "+"),
+            MediaWikiEmitter::group_digits(delta)
+        );
+
+        if delta >= 0 || len_old == 0 {
+            return format!("({}) ", delta_str);
+        }
+
+        let removed_percent = -delta * 100 / len_old;
+
+        if len_new == 0 {
+            return format!("(<b>{}, page blanked</b>) ", delta_str);
+        }
+
+        if removed_percent > i64::from(self.large_removal_percent) {
+            return format!(
+                "(<b>{}, removed {}% of the page, possible blanking</b>) ",
+                delta_str,
+                removed_percent
+            );
+        }
+
+        format!("({}) ", delta_str)
+    }
+
+    fn explain_page_size(evt: &json::JsonValue) -> String {
+        match evt["length"]["new"].as_i64() {
+            Some(len_new) => format!("({} bytes) ", MediaWikiEmitter::group_digits(len_new)),
+            None => String::new()
+        }
+    }
+
+    fn truncate_chars(text: &str, max_chars: usize) -> String {
+        if text.chars().count() <= max_chars {
+            return text.to_string();
+        }
+
+        format!("{}…", text.chars().take(max_chars).collect::<String>())
+    }
+
+    // Changed lines of an edit, only fetched when excerpts are shown
+    // or the content is inspected for risk scoring.
+    fn load_diff(&self, evt_previd: u32, evt_curid: u32) -> Option<Vec<DiffLine>> {
+        if !(self.diff_excerpts || self.risk_scorer.inspects_diff()) || evt_previd == 0 {
+            return None;
+        }
+
+        let diff_lines = self.wiki_api.get_diff_lines(evt_previd, evt_curid);
+
+        if diff_lines.is_none() {
+            eprintln!(
+                "Failed to obtain diff for rev_id='{}', old_rev_id='{}'",
+                evt_curid, evt_previd
+            );
+        }
+
+        diff_lines
+    }
+
+    // Lines of a new page, as if added by a diff, only fetched when the
+    // content is inspected for risk scoring.
+    fn load_new_page(&self, evt_curid: u32) -> Option<Vec<DiffLine>> {
+        if !self.risk_scorer.inspects_diff() {
+            return None;
+        }
+
+        let content = match self.wiki_api.get_revision_content(evt_curid) {
+            Some(content) => content,
+            None => {
+                eprintln!("Failed to obtain content of rev_id='{}'", evt_curid);
+
+                return None;
+            }
+        };
+
+        let lines = content
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| DiffLine::Added(line.to_string()))
+            .collect::<Vec<DiffLine>>();
+
+        Some(lines)
+    }
+
+    // Short excerpt of the changed lines of an edit, empty when
+    // disabled or when the diff can't be obtained.
+    fn explain_diff(&self, diff_lines: Option<&[DiffLine]>) -> String {
+        let diff_lines = match diff_lines {
+            Some(diff_lines) if self.diff_excerpts && !diff_lines.is_empty() => diff_lines,
+            _ => return String::new()
+        };
+
+        let excerpt = diff_lines
+            .iter()
+            .take(self.diff_excerpt_lines)
+            .map(|diff_line| {
+                let (sign, text) = match diff_line {
+                    DiffLine::Added(text) => ("+", text),
+                    DiffLine::Removed(text) => ("−", text)
+                };
+
+                format!(
+                    "{} {}",
+                    sign,
+                    htmlescape_str(MediaWikiEmitter::truncate_chars(text, self.diff_excerpt_line_length))
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        let overflow = diff_lines.len().saturating_sub(self.diff_excerpt_lines);
+
+        format!(
+            "\n<pre>{}</pre>{}",
+            excerpt,
+            MediaWikiEmitter::cond_string(
+                overflow > 0,
+                &format!("\n… and {} more changed lines", overflow),
+                "",
+            )
+        )
+    }
+
+    fn handle_evt_categorize(&self, evt: &json::JsonValue) {
+        if !self.categorize_enabled {
+            return;
+        }
+
+        let user = evt["user"].to_string();
+        let category = evt["title"].to_string();
+        let comment = evt["comment"].to_string();
+
+        let msg = match self.categorize_rgx.captures(&comment) {
+            Some(caps) => format!(
+                r#"[categorize] <a href="{}">{}</a> {} <a href="{}">{}</a> {} <a href="{}">{}</a>"#,
+                self.get_user_url(&user),
+                htmlescape_str(&user),
+                MediaWikiEmitter::cond_string(&caps[2] == "added to", "added", "removed"),
+                self.get_url(&caps[1]),
+                htmlescape_str(&caps[1]),
+                MediaWikiEmitter::cond_string(&caps[2] == "added to", "to", "from"),
+                self.get_url(&category),
+                htmlescape_str(&category)
+            ),
+            None => format!(
+                r#"[categorize] <a href="{}">{}</a> changed members of <a href="{}">{}</a>: {}"#,
+                self.get_user_url(&user),
+                htmlescape_str(&user),
+                self.get_url(&category),
+                htmlescape_str(&category),
+                self.wikitext.render(&comment, &category)
+            )
+        };
+
+        self.emit(evt, msg, false);
+    }
+
+    // changes originating from other wikis (e.g. Wikidata)
+    fn handle_evt_external(&self, evt: &json::JsonValue) {
+        let user = evt["user"].to_string();
+        let page = evt["title"].to_string();
+        let comment = evt["comment"].to_string();
+
+        let msg = format!(
+            r#"[external] {} changed <a href="{}">{}</a> {}"#,
+            user,
+            self.get_url(&page),
+            page,
+            self.explain_comment(&comment, &page)
+        );
+
+        self.emit(evt, msg, false);
+    }
+
+    // Tag the message of a risky edit with a badge, and bring high-risk
+    // edits to the attention of the moderators.
+    fn emit_assessed(&self, evt: &json::JsonValue, msg: String, diff_lines: Option<&[DiffLine]>) {
+        let is_flooding = !self.check_flood(evt);
+
+        let assessment = match self.risk_scorer.is_enabled() {
+            true => Some(self.risk_scorer.assess(evt, diff_lines)),
+            false => None,
+        };
+
+        let level = assessment.as_ref().map_or(RiskLevel::Low, |assessment| assessment.level);
+
+        let watchers = self.watchers(evt);
+
+        // risky and watched edits are posted even from flooding ranges
+        if is_flooding && level == RiskLevel::Low && watchers.is_empty() {
+            return;
+        }
+
+        let msg = match assessment {
+            Some(ref assessment) => format!("{}{}", assessment.badge(), msg),
+            None => msg,
+        };
+
+        let reply_markup = self.action_keyboard(evt);
+
+        if let (RiskLevel::High, Some(channel_id)) = (level, self.moderators_channel_id) {
+            self.configured_api.emit_with_markup(channel_id, msg.clone(), true, reply_markup.clone());
+        }
+
+        // risky edits are always posted on their own
+        match level {
+            RiskLevel::Low => self.emit_or_digest(evt, watchers, msg, None, true, reply_markup),
+            _ => self.post(evt, watchers, msg, None, true, reply_markup),
+        }
+    }
+
+    // Buttons to moderate the revision of an edit or page creation
+    fn action_keyboard(&self, evt: &json::JsonValue) -> Option<telegram_bot::InlineKeyboardMarkup> {
+        if !self.actions_enabled {
+            return None;
+        }
+
+        let rev_id = evt["revision"]["new"].as_u32()?;
+
+        let mut available = Vec::new();
+
+        if evt["patrolled"] != true {
+            available.push(WikiAction::Patrol);
+        }
+
+        // page creations can't be rolled back; whether the edit is still
+        // the latest one is only checked once rollback is pressed
+        if evt["type"] == "edit" {
+            available.push(WikiAction::Rollback);
+        }
+
+        available.push(WikiAction::Approve);
+
+        Some(bot::action_keyboard(self.wiki_idx, rev_id, &available))
     }
 
     fn handle_evt_edit(&self, evt: &json::JsonValue) {
@@ -364,10 +1216,12 @@ impl MediaWikiEmitter {
         let evt_is_patrolled = evt["patrolled"].as_bool().unwrap();
         let evt_is_bot = evt["bot"].as_bool().unwrap();
 
-        let url = format!(
-            "https://psychonautwiki.org/w/index.php?title={}&type=revision&diff={:?}&oldid={:?}",
-            self.wrap_urlencode(&MediaWikiEmitter::urlencode(&page)), evt_curid, evt_previd
-        );
+        let url = self.get_index_url(&[
+            ("title", &page),
+            ("type", "revision"),
+            ("diff", &evt_curid.to_string()),
+            ("oldid", &evt_previd.to_string()),
+        ]);
 
         let has_flags = evt_is_minor || evt_is_patrolled || evt_is_bot;
 
@@ -378,8 +1232,10 @@ impl MediaWikiEmitter {
             MediaWikiEmitter::cond_string(evt_is_bot, "<b>bot</b> ", "")
         );
 
+        let diff_lines = self.load_diff(evt_previd, evt_curid);
+
         let msg = format!(
-            r#"{}<a href="{}">{}</a> edited <a href="{}">{}</a> {}"#,
+            r#"{}<a href="{}">{}</a>{} edited <a href="{}">{}</a> {}{}{}"#,
             MediaWikiEmitter::cond_string(
                 has_flags,
                 &format!("| {}| ", flags),
@@ -387,12 +1243,15 @@ impl MediaWikiEmitter {
             ),
             self.get_user_url(&user),
             user,
+            self.explain_editor(&user),
             url,
             page,
-            MediaWikiEmitter::explain_comment(&comment)
+            self.explain_size_delta(evt),
+            self.explain_comment(&comment, &page),
+            self.explain_diff(diff_lines.as_deref())
         );
 
-        self.configured_api.emit(msg, true);
+        self.emit_assessed(evt, msg, diff_lines.as_deref());
     }
 
     fn handle_evt_new(&self, evt: &json::JsonValue) {
@@ -406,111 +1265,422 @@ impl MediaWikiEmitter {
         let evt_is_patrolled = evt["patrolled"].as_bool().unwrap();
         let evt_is_bot = evt["bot"].as_bool().unwrap();
 
-        let url = format!(
-            "https://psychonautwiki.org/w/index.php?title={}&oldid={:?}",
-            self.wrap_urlencode(&MediaWikiEmitter::urlencode(&page)), evt_curid
+        let url = self.get_index_url(&[
+            ("title", &page),
+            ("oldid", &evt_curid.to_string()),
+        ]);
+
+        let has_flags = evt_is_minor || evt_is_patrolled || evt_is_bot;
+
+        let flags = format!(
+            "{}{}{}",
+            MediaWikiEmitter::cond_string(evt_is_minor, "<b>minor</b> ", ""),
+            MediaWikiEmitter::cond_string(evt_is_patrolled, "<b>patrolled</b> ", ""),
+            MediaWikiEmitter::cond_string(evt_is_bot, "<b>bot</b> ", "")
+        );
+
+        let msg = format!(
+            r#"[new] {}<a href="{}">{}</a>{} created page <a href="{}">{}</a> {}{}"#,
+            MediaWikiEmitter::cond_string(
+                has_flags,
+                &format!("| {}| ", flags),
+                "",
+            ),
+            self.get_user_url(&user),
+            user,
+            self.explain_editor(&user),
+            url,
+            page,
+            MediaWikiEmitter::explain_page_size(evt),
+            self.explain_comment(&comment, &page)
+        );
+
+        let page_lines = self.load_new_page(evt_curid);
+
+        self.emit_assessed(evt, msg, page_lines.as_deref());
+    }
+
+    fn handle_evt_log(&self, evt: &json::JsonValue) {
+        ;
+
+        let log_type = evt["log_type"].to_string();
+
+        match &*log_type {
+            "approval" => self.handle_evt_log_approval(evt),
+            "abusefilter" => self.handle_evt_log_abusefilter(evt),
+            "avatar" => self.handle_evt_log_avatar(evt),
+            "block" => self.handle_evt_log_block(evt),
+            "contentmodel" => self.handle_evt_log_contentmodel(evt),
+            "delete" => self.handle_evt_log_delete(evt),
+            "import" => self.handle_evt_log_import(evt),
+            "interwiki" => self.handle_evt_log_interwiki(evt),
+            "managetags" => self.handle_evt_log_managetags(evt),
+            "merge" => self.handle_evt_log_merge(evt),
+            "move" => self.handle_evt_log_move(evt),
+            "newusers" => self.handle_evt_log_newusers(evt),
+            "patrol" => self.handle_evt_log_patrol(evt),
+            "profile" => self.handle_evt_log_profile(evt),
+            "protect" => self.handle_evt_log_protect(evt),
+            "renameuser" => self.handle_evt_log_renameuser(evt),
+            "rights" => self.handle_evt_log_rights(evt),
+            "thanks" => self.handle_evt_log_thanks(evt),
+            "upload" => self.handle_evt_log_upload(evt),
+            "usermerge" => self.handle_evt_log_usermerge(evt),
+            _ => {
+                if log_type == "null" {
+                    return;
+                }
+
+                let msg = format!(
+                    "[log_not_implemented] {}",
+                    htmlescape_str(evt.dump())
+                );
+
+                self.emit(evt, msg, true);
+            }
+        }
+    }
+
+    fn handle_evt_log_avatar(&self, evt: &json::JsonValue) {
+        let user = evt["user"].to_string();
+        let comment = evt["comment"].to_string();
+
+        let msg = format!(
+            r#"[log/avatar] <a href="{}">{}</a> {}"#,
+            self.get_user_url(&user),
+            user,
+            self.wikitext.render(&comment, &evt["title"].to_string())
         );
 
-        let has_flags = evt_is_minor || evt_is_patrolled || evt_is_bot;
+        self.emit(evt, msg, true);
+    }
+
+    fn handle_evt_log_abusefilter(&self, evt: &json::JsonValue) {
+        let log_action = evt["log_action"].to_string();
+
+        let user = evt["user"].to_string();
+        let page = evt["title"].to_string();
+
+        let log_params = &evt["log_params"];
+
+        let msg = match &*log_action {
+            "hit" => format!(
+                r#"[log/abusefilter] <a href="{}">{}</a> triggered {} on <a href="{}">{}</a>{}"#,
+                self.get_user_url(&user),
+                user,
+                match MediaWikiEmitter::log_param(log_params, "filter") {
+                    Some(filter) => format!(
+                        r#"<a href="{}">filter {}</a>"#,
+                        self.get_url(&format!("Special:AbuseFilter/{}", log_params["filter"])),
+                        filter
+                    ),
+                    None => "a filter".to_string(),
+                },
+                self.get_url(&page),
+                page,
+                MediaWikiEmitter::log_param(log_params, "actions")
+                    .map(|actions| format!(" (actions taken: {})", actions))
+                    .unwrap_or_default()
+            ),
+            // filter changes are logged against "Special:AbuseFilter/<id>"
+            _ => format!(
+                r#"[log/abusefilter/{}] <a href="{}">{}</a> changed <a href="{}">{}</a>"#,
+                log_action,
+                self.get_user_url(&user),
+                user,
+                self.get_url(&page),
+                page
+            )
+        };
+
+        self.emit(evt, msg, true);
+    }
+
+    // Block parameters are keyed "5::duration" and "6::flags" in raw
+    // log entries, and "duration" and "flags" once formatted
+    fn block_param<'a>(log_params: &'a json::JsonValue, name: &str, idx: u32) -> &'a json::JsonValue {
+        match log_params[name].is_null() {
+            true => &log_params[format!("{}::{}", idx, name).as_str()],
+            false => &log_params[name],
+        }
+    }
+
+    // "nocreate,noautoblock" or ["nocreate", "noautoblock"] -> "account creation disabled, autoblock disabled"
+    fn explain_block_flags(flags: &json::JsonValue) -> Vec<String> {
+        let flags = match flags.is_array() {
+            true => flags.members().map(|flag| flag.to_string()).collect::<Vec<String>>(),
+            false => flags.as_str().unwrap_or("").split(',').map(str::to_string).collect(),
+        };
+
+        flags
+            .iter()
+            .map(|flag| flag.trim())
+            .filter(|flag| !flag.is_empty())
+            .map(|flag| match flag {
+                "nocreate" => "account creation disabled".to_string(),
+                "noautoblock" => "autoblock disabled".to_string(),
+                "noemail" => "e-mail disabled".to_string(),
+                "nousertalk" => "own talk page disabled".to_string(),
+                "anononly" => "anonymous users only".to_string(),
+                "hiddenname" => "username hidden".to_string(),
+                flag => flag.to_string(),
+            })
+            .collect()
+    }
+
+    fn handle_evt_log_block(&self, evt: &json::JsonValue) {
+        let log_action = evt["log_action"].to_string();
+
+        let user = evt["user"].to_string();
+        let comment = evt["comment"].to_string();
+
+        // blocks are logged against "User:<target>"
+        let title = evt["title"].to_string();
+        let target = match title.find(':') {
+            Some(idx) => title[idx + 1..].to_string(),
+            None => title.clone(),
+        };
+
+        let log_params = &evt["log_params"];
+
+        let block_list_url = self.get_index_url(&[
+            ("title", "Special:BlockList"),
+            ("wpTarget", &target),
+        ]);
+
+        let (tag, verb) = match &*log_action {
+            "unblock" => ("[log/ban/unblock]", "unblocked"),
+            "reblock" => ("[log/ban/reblock]", "changed the block of"),
+            _ if log_params["sitewide"] == false => ("[log/ban]", "partially blocked"),
+            _ => ("[log/ban]", "blocked")
+        };
+
+        let details = match &*log_action {
+            "unblock" => String::new(),
+            _ => {
+                let duration = MediaWikiEmitter::block_param(log_params, "duration", 5).to_string();
+
+                let is_indefinite = duration.is_empty()
+                    || duration == "infinite"
+                    || duration == "indefinite"
+                    || duration == "infinity";
+
+                let mut details = match log_params["expiry"].as_str() {
+                    Some(expiry) if !is_indefinite => vec![format!("until {}", expiry)],
+                    _ => Vec::new(),
+                };
+
+                details.extend(MediaWikiEmitter::explain_block_flags(
+                    MediaWikiEmitter::block_param(log_params, "flags", 6)
+                ));
+
+                format!(
+                    " {}{}",
+                    match is_indefinite {
+                        true => "<b>indefinitely</b>".to_string(),
+                        false => format!("for <b>{}</b>", htmlescape_str(duration)),
+                    },
+                    MediaWikiEmitter::cond_string(
+                        !details.is_empty(),
+                        &format!(" ({})", details.join(", ")),
+                        "",
+                    )
+                )
+            }
+        };
+
+        let msg = format!(
+            r#"{} <a href="{}">{}</a> {} <a href="{}">{}</a>{} {} [<a href="{}">block list</a>]"#,
+            tag,
+            self.get_user_url(&user),
+            user,
+            verb,
+            self.get_user_url(&target),
+            target,
+            details,
+            self.explain_comment(&comment, &title),
+            block_list_url
+        );
+
+        self.emit(evt, msg, true);
+    }
+
+    fn handle_evt_log_contentmodel(&self, evt: &json::JsonValue) {
+        let user = evt["user"].to_string();
+        let page = evt["title"].to_string();
+        let comment = evt["comment"].to_string();
+
+        let log_params = &evt["log_params"];
+
+        let msg = match evt["log_action"].as_str() {
+            Some("new") => format!(
+                r#"[log/contentmodel] <a href="{}">{}</a> created <a href="{}">{}</a>{} {}"#,
+                self.get_user_url(&user),
+                user,
+                self.get_url(&page),
+                page,
+                MediaWikiEmitter::log_param(log_params, "newmodel")
+                    .map(|newmodel| format!(" with content model {}", newmodel))
+                    .unwrap_or_default(),
+                self.explain_comment(&comment, &page)
+            ),
+            _ => format!(
+                r#"[log/contentmodel] <a href="{}">{}</a> changed the content model of <a href="{}">{}</a>{}{} {}"#,
+                self.get_user_url(&user),
+                user,
+                self.get_url(&page),
+                page,
+                MediaWikiEmitter::log_param(log_params, "oldmodel")
+                    .map(|oldmodel| format!(" from {}", oldmodel))
+                    .unwrap_or_default(),
+                MediaWikiEmitter::log_param(log_params, "newmodel")
+                    .map(|newmodel| format!(" to {}", newmodel))
+                    .unwrap_or_default(),
+                self.explain_comment(&comment, &page)
+            )
+        };
+
+        self.emit(evt, msg, true);
+    }
+
+    fn handle_evt_log_delete(&self, evt: &json::JsonValue) {
+        let log_action = evt["log_action"].to_string();
+
+        let user = evt["user"].to_string();
+        let page = evt["title"].to_string();
+        let comment = evt["comment"].to_string();
 
-        let flags = format!(
-            "{}{}{}",
-            MediaWikiEmitter::cond_string(evt_is_minor, "<b>minor</b> ", ""),
-            MediaWikiEmitter::cond_string(evt_is_patrolled, "<b>patrolled</b> ", ""),
-            MediaWikiEmitter::cond_string(evt_is_bot, "<b>bot</b> ", "")
-        );
+        let (tag, verb) = match &*log_action {
+            "restore" => ("[log/delete/restore]", "restored page:"),
+            "revision" => ("[log/delete/revision]", "changed revision visibility of"),
+            "event" => ("[log/delete/event]", "changed log entry visibility of"),
+            "delete_redir" => ("[log/delete]", "deleted redirect:"),
+            _ => ("[log/delete]", "deleted page:")
+        };
 
         let msg = format!(
-            r#"[new] {}<a href="{}">{}</a> created page <a href="{}">{}</a> {}"#,
-            MediaWikiEmitter::cond_string(
-                has_flags,
-                &format!("| {}| ", flags),
-                "",
-            ),
+            r#"{} <a href="{}">{}</a> {} <a href="{}">{}</a> {}"#,
+            tag,
             self.get_user_url(&user),
             user,
-            url,
+            verb,
+            self.get_url(&page),
             page,
-            MediaWikiEmitter::explain_comment(&comment)
+            self.explain_comment(&comment, &page)
         );
 
-        self.configured_api.emit(msg, true);
+        self.emit(evt, msg, true);
     }
 
-    fn handle_evt_log(&self, evt: &json::JsonValue) {
-        ;
+    fn handle_evt_log_import(&self, evt: &json::JsonValue) {
+        let user = evt["user"].to_string();
+        let page = evt["title"].to_string();
 
-        let log_type = evt["log_type"].to_string();
+        let log_params = &evt["log_params"];
 
-        match &*log_type {
-            "approval" => self.handle_evt_log_approval(evt),
-            "avatar" => self.handle_evt_log_avatar(evt),
-            "block" => self.handle_evt_log_block(evt),
-            "delete" => self.handle_evt_log_delete(evt),
-            "move" => self.handle_evt_log_move(evt),
-            "newusers" => self.handle_evt_log_newusers(evt),
-            "patrol" => self.handle_evt_log_patrol(evt),
-            "profile" => self.handle_evt_log_profile(evt),
-            "rights" => self.handle_evt_log_rights(evt),
-            "thanks" => self.handle_evt_log_thanks(evt),
-            "upload" => self.handle_evt_log_upload(evt),
-            "usermerge" => self.handle_evt_log_usermerge(evt),
-            _ => {
-                if log_type == "null" {
-                    return;
-                }
+        let source = match evt["log_action"].as_str() {
+            Some("interwiki") => MediaWikiEmitter::log_param(log_params, "interwiki_title")
+                .map(|interwiki_title| format!(" from {}", interwiki_title))
+                .unwrap_or_else(|| " from another wiki".to_string()),
+            _ => " from an uploaded file".to_string()
+        };
 
-                let msg = format!(
-                    "[log_not_implemented] {}",
-                    self.emitter_rgx.plusexclquest_to_url(&evt.dump())
-                );
+        let msg = format!(
+            r#"[log/import] <a href="{}">{}</a> imported {} into <a href="{}">{}</a>{}"#,
+            self.get_user_url(&user),
+            user,
+            MediaWikiEmitter::cond_string(
+                log_params["count"].is_number(),
+                &format!("{} revisions", log_params["count"]),
+                "revisions",
+            ),
+            self.get_url(&page),
+            page,
+            source
+        );
 
-                self.configured_api.emit(msg, true);
-            }
-        }
+        self.emit(evt, msg, true);
     }
 
-    fn handle_evt_log_avatar(&self, evt: &json::JsonValue) {
+    fn handle_evt_log_interwiki(&self, evt: &json::JsonValue) {
         let user = evt["user"].to_string();
-        let comment = evt["comment"].to_string();
+
+        let log_params = &evt["log_params"];
+
+        let verb = match evt["log_action"].as_str() {
+            Some("iw_add") => "added",
+            Some("iw_delete") => "removed",
+            _ => "edited"
+        };
 
         let msg = format!(
-            r#"[log/avatar] <a href="{}">{}</a> {}"#,
+            r#"[log/interwiki] <a href="{}">{}</a> {} interwiki prefix{}{}"#,
             self.get_user_url(&user),
             user,
-            comment
+            verb,
+            MediaWikiEmitter::log_param(log_params, "prefix")
+                .map(|prefix| format!(r#" "{}""#, prefix))
+                .unwrap_or_default(),
+            MediaWikiEmitter::log_param(log_params, "url")
+                .map(|url| format!(" ({})", url))
+                .unwrap_or_default()
         );
 
-        self.configured_api.emit(msg, true);
+        self.emit(evt, msg, true);
     }
 
-    fn handle_evt_log_block(&self, evt: &json::JsonValue) {
+    fn handle_evt_log_managetags(&self, evt: &json::JsonValue) {
         let user = evt["user"].to_string();
-        let comment = evt["log_action_comment"].to_string();
+        let comment = evt["comment"].to_string();
+
+        let verb = match evt["log_action"].as_str() {
+            Some("create") => "created",
+            Some("delete") => "deleted",
+            Some("activate") => "activated",
+            Some("deactivate") => "deactivated",
+            _ => "changed"
+        };
 
         let msg = format!(
-            r#"[log/ban] <a href="{}">{}</a> {}"#,
+            r#"[log/managetags] <a href="{}">{}</a> {} tag{} {}"#,
             self.get_user_url(&user),
             user,
-            comment
+            verb,
+            MediaWikiEmitter::log_param(&evt["log_params"], "tag")
+                .map(|tag| format!(r#" "{}""#, tag))
+                .unwrap_or_default(),
+            self.explain_comment(&comment, &evt["title"].to_string())
         );
 
-        self.configured_api.emit(msg, true);
+        self.emit(evt, msg, true);
     }
 
-    fn handle_evt_log_delete(&self, evt: &json::JsonValue) {
+    fn handle_evt_log_merge(&self, evt: &json::JsonValue) {
         let user = evt["user"].to_string();
         let page = evt["title"].to_string();
 
+        let log_params = &evt["log_params"];
+
         let msg = format!(
-            r#"[log/delete] <a href="{}">{}</a> deleted page: <a href="{}">{}</a>"#,
+            r#"[log/merge] <a href="{}">{}</a> merged the history of <a href="{}">{}</a>{}{}"#,
             self.get_user_url(&user),
             user,
             self.get_url(&page),
-            page
+            page,
+            match log_params["dest_title"].as_str() {
+                Some(evt_dest) => format!(
+                    r#" into <a href="{}">{}</a>"#,
+                    self.get_url(evt_dest),
+                    htmlescape_str(evt_dest)
+                ),
+                None => String::new(),
+            },
+            MediaWikiEmitter::log_param(log_params, "mergepoint")
+                .map(|mergepoint| format!(" (revisions up to {})", mergepoint))
+                .unwrap_or_default()
         );
 
-        self.configured_api.emit(msg, true);
+        self.emit(evt, msg, true);
     }
 
     fn handle_evt_log_move(&self, evt: &json::JsonValue) {
@@ -529,10 +1699,12 @@ impl MediaWikiEmitter {
             evt_target
         );
 
-        self.configured_api.emit(msg, true);
+        self.emit(evt, msg, true);
     }
 
     fn handle_evt_log_newusers(&self, evt: &json::JsonValue) {
+        self.risk_scorer.note_registration(evt);
+
         let comment = evt["log_action_comment"].to_string();
 
         let user = evt["user"].to_string();
@@ -541,10 +1713,10 @@ impl MediaWikiEmitter {
             r#"[log/newusers] <a href="{}">{}</a> {}"#,
             self.get_user_url(&user),
             user,
-            comment
+            self.wikitext.render(&comment, &evt["title"].to_string())
         );
 
-        self.configured_api.emit(msg, true);
+        self.emit(evt, msg, true);
     }
 
     fn handle_evt_log_approval(&self, evt: &json::JsonValue) {
@@ -560,151 +1732,180 @@ impl MediaWikiEmitter {
 
                 let msg = format!(
                     "[log/approval/not_implemented] {}",
-                    self.emitter_rgx.plusexclquest_to_url(&evt.dump())
+                    htmlescape_str(evt.dump())
                 );
 
-                self.configured_api.emit(msg, true);
+                self.emit(evt, msg, true);
             }
         }
     }
 
-    fn handle_evt_log_approval_approve(&self, evt: &json::JsonValue) {
-        let params: Option<(String, Option<String>, Option<String>)> = serde_json::from_str(&evt["log_params"].dump()).ok();
-
-        // parse second and third value of params as revid and oldrevid respectively
-        // if not present, set both to None
-        let (evt_revid, evt_oldrevid) = {
-            if !params.is_some() {
-                (None, None)
-            } else {
-                let params = params.unwrap();
+    // Revision id given as number or numeric string
+    fn parse_rev_id(value: &json::JsonValue) -> Option<u32> {
+        value
+            .as_u32()
+            .or_else(|| value.as_str().and_then(|rev_id| rev_id.trim().parse::<u32>().ok()))
+    }
 
-                // parse to u32
+    // Approved and previously approved revision of an approval. Depending
+    // on the version of Approved Revs, the log parameters are positional,
+    // e.g. ["", "1234", "1200"], or keyed, e.g. {"rev_id": 1234,
+    // "old_rev_id": 1200}, possibly with "4::"-style key prefixes.
+    fn approval_rev_ids(evt: &json::JsonValue) -> (Option<u32>, Option<u32>) {
+        let log_params = match evt["log_params"] {
+            // serialized by some feeds
+            json::JsonValue::String(_) | json::JsonValue::Short(_) =>
+                json::parse(evt["log_params"].as_str().unwrap_or("")).unwrap_or(json::JsonValue::Null),
+            ref log_params => log_params.clone(),
+        };
 
-                let evt_revid = params.1.map(|x| x.parse::<u32>().ok()).flatten();
-                let evt_oldrevid = params.2.map(|x| x.parse::<u32>().ok()).flatten();
+        if log_params.is_array() {
+            return (
+                MediaWikiEmitter::parse_rev_id(&log_params[1]),
+                MediaWikiEmitter::parse_rev_id(&log_params[2]),
+            );
+        }
 
-                (evt_revid, evt_oldrevid)
-            }
+        let find_rev_id = |names: &[&str]| {
+            log_params
+                .entries()
+                .find(|(key, _)| names.contains(&key.rsplit("::").next().unwrap_or(key)))
+                .and_then(|(_, value)| MediaWikiEmitter::parse_rev_id(value))
         };
 
-        if evt_revid.is_none() || evt_oldrevid.is_none() {
-            eprintln!(
-                "Failed to obtain revision information for page='{}', rev_id='{:?}', old_rev_id='{:?}' [either rev_id or old_rev_id is missing]",
-                evt["title"].to_string(), evt_revid, evt_oldrevid
-            );
+        (
+            find_rev_id(&["rev_id", "revid"]),
+            find_rev_id(&["old_rev_id", "oldrevid", "oldid"]),
+        )
+    }
 
-            return;
+    // Formerly approved revision of a revoked approval
+    fn unapproval_rev_id(evt: &json::JsonValue) -> Option<u32> {
+        let (rev_id, old_rev_id) = MediaWikiEmitter::approval_rev_ids(evt);
+
+        old_rev_id.or(rev_id)
+    }
+
+    // e.g. ` by <a href="…">User</a> ("summary")`, empty if unknown
+    fn explain_rev_author(&self, rev_info: Option<&RevInfo>, page: &str) -> String {
+        match rev_info {
+            Some(rev_info) => format!(
+                r#" by <a href="{}">{}</a> ("{}")"#,
+                self.get_user_url(&rev_info.0),
+                rev_info.0,
+                self.wikitext.render(&rev_info.1, page)
+            ),
+            None => String::new(),
         }
+    }
 
-        let evt_revid = evt_revid.unwrap();
-        let evt_oldrevid = evt_oldrevid.unwrap();
+    fn handle_evt_log_approval_approve(&self, evt: &json::JsonValue) {
+        let (evt_revid, evt_oldrevid) = MediaWikiEmitter::approval_rev_ids(evt);
 
         let user = evt["user"].to_string();
         let page = evt["title"].to_string();
 
-        let rev_info: Option<RevInfo> = get_revision_info(page.clone(), evt_revid.to_string());
+        // still announce approvals whose parameters can't be made sense of
+        let evt_revid = match evt_revid {
+            Some(evt_revid) => evt_revid,
+            None => {
+                eprintln!(
+                    "Failed to obtain the approved revision for page='{}', log_params='{}'",
+                    page, evt["log_params"].dump()
+                );
 
-        let has_rev_info = rev_info.is_some();
+                let msg = format!(
+                    r#"[log/approval] <a href="{}">{}</a> approved <a href="{}">{}</a>"#,
+                    self.get_user_url(&user),
+                    user,
+                    self.get_url(&page),
+                    page
+                );
 
-        if !has_rev_info {
+                return self.emit(evt, msg, true);
+            }
+        };
+
+        let rev_info: Option<RevInfo> = self.get_revision_info(evt_revid);
+
+        if rev_info.is_none() {
             eprintln!(
                 "Failed to obtain revision information for page='{}', rev_id='{}'",
                 page, evt_revid
             );
         }
 
-        // extract user and comment from revision data
-        let (rev_by_user, rev_comment, rev_parentid) = {
-            if !has_rev_info {
-                (String::new(), String::new(), evt_oldrevid.to_string())
-            } else {
-                let rev_info = rev_info.unwrap();
-
-                (rev_info.0, rev_info.1, rev_info.2)
-            }
-        };
-
-        let rev_info_msg_user = {
-            if !has_rev_info {
-                String::new()
-            } else {
-                format!(
-                    r#" by <a href="{}">{}</a> ("{}")"#,
-                    self.get_user_url(&rev_by_user),
-                    rev_by_user,
-                    rev_comment
-                )
-            }
+        // the parent revision, or else the previously approved one
+        let rev_parentid = rev_info
+            .as_ref()
+            .map(|rev_info| rev_info.2.clone())
+            .or_else(|| evt_oldrevid.map(|evt_oldrevid| evt_oldrevid.to_string()));
+
+        let evt_revid_str = evt_revid.to_string();
+
+        let url = match rev_parentid {
+            Some(ref rev_parentid) => self.get_index_url(&[
+                ("title", &page),
+                ("type", "revision"),
+                ("diff", &evt_revid_str),
+                ("oldid", rev_parentid),
+            ]),
+            None => self.get_index_url(&[
+                ("title", &page),
+                ("oldid", &evt_revid_str),
+            ]),
         };
 
-        let url = format!(
-            "https://psychonautwiki.org/w/index.php?title={}&type=revision&diff={:?}&oldid={}",
-            self.wrap_urlencode(&MediaWikiEmitter::urlencode(&page)), evt_revid, rev_parentid
-        );
-
         let msg = format!(
             r#"[log/approval] <a href="{}">{}</a> approved <a href="{}">revision {}</a>{} of <a href="{}">{}</a>"#,
             self.get_user_url(&user),
             user,
             url,
             evt_revid,
-            rev_info_msg_user,
+            self.explain_rev_author(rev_info.as_ref(), &page),
             self.get_url(&page),
             page
         );
 
-        self.configured_api.emit(msg, true);
+        self.emit(evt, msg, true);
     }
 
     // Currently “unapprove" will unapprove all approved revisions of
     // an article and effectively blank it. Therefore the old revision
     // id will only be used to link to the previously approved revision.
     fn handle_evt_log_approval_unapprove(&self, evt: &json::JsonValue) {
-        let evt_oldrevid = evt["log_params"]["old_rev_id"].as_u32().unwrap();
-
         let user = evt["user"].to_string();
         let page = evt["title"].to_string();
 
-        let rev_info: Option<RevInfo> = get_revision_info(page.clone(), evt_oldrevid.to_string());
+        let evt_oldrevid = match MediaWikiEmitter::unapproval_rev_id(evt) {
+            Some(evt_oldrevid) => evt_oldrevid,
+            None => {
+                let msg = format!(
+                    r#"[log/approval] <a href="{}">{}</a> revoked the approval of <a href="{}">{}</a>"#,
+                    self.get_user_url(&user),
+                    user,
+                    self.get_url(&page),
+                    page
+                );
 
-        let has_rev_info = rev_info.is_some();
+                return self.emit(evt, msg, true);
+            }
+        };
 
-        if !has_rev_info {
+        let rev_info: Option<RevInfo> = self.get_revision_info(evt_oldrevid);
+
+        if rev_info.is_none() {
             eprintln!(
                 "Failed to obtain revision information for page='{}', rev_id='{}'",
                 page, evt_oldrevid
             );
         }
 
-        // extract user and comment from revision data
-        let (rev_by_user, rev_comment) = {
-            if !has_rev_info {
-                (String::new(), String::new())
-            } else {
-                let rev_info = rev_info.unwrap();
-
-                (rev_info.0, rev_info.1)
-            }
-        };
-
-        let rev_info_msg_user = {
-            if !has_rev_info {
-                String::new()
-            } else {
-                format!(
-                    r#" by <a href="{}">{}</a> ("{}")"#,
-                    self.get_user_url(&rev_by_user),
-                    rev_by_user,
-                    rev_comment
-                )
-            }
-        };
-
-        let url = format!(
-            "https://psychonautwiki.org/w/index.php?title={}&type=revision&oldid={}",
-            self.wrap_urlencode(&MediaWikiEmitter::urlencode(&page)), evt_oldrevid
-        );
+        let url = self.get_index_url(&[
+            ("title", &page),
+            ("type", "revision"),
+            ("oldid", &evt_oldrevid.to_string()),
+        ]);
 
         let msg = format!(
             r#"[log/approval] <a href="{}">{}</a> revoked the approval of <a href="{}">{}</a> (was <a href="{}">revision {}</a>{})"#,
@@ -714,10 +1915,10 @@ impl MediaWikiEmitter {
             page,
             url,
             evt_oldrevid,
-            rev_info_msg_user
+            self.explain_rev_author(rev_info.as_ref(), &page)
         );
 
-        self.configured_api.emit(msg, true);
+        self.emit(evt, msg, true);
     }
 
     fn handle_evt_log_patrol(&self, evt: &json::JsonValue) {
@@ -737,7 +1938,7 @@ impl MediaWikiEmitter {
         let user = evt["user"].to_string();
         let page = evt["title"].to_string();
 
-        let rev_info: Option<RevInfo> = get_revision_info(page.clone(), evt_curid.to_string());
+        let rev_info: Option<RevInfo> = self.get_revision_info(evt_curid);
 
         let has_rev_info = rev_info.is_some();
 
@@ -767,15 +1968,17 @@ impl MediaWikiEmitter {
                     r#" by <a href="{}">{}</a> ("{}")"#,
                     self.get_user_url(&rev_by_user),
                     rev_by_user,
-                    rev_comment
+                    self.wikitext.render(&rev_comment, &page)
                 )
             }
         };
 
-        let url = format!(
-            "https://psychonautwiki.org/w/index.php?title={}&type=revision&diff={:?}&oldid={:?}",
-            self.wrap_urlencode(&MediaWikiEmitter::urlencode(&page)), evt_curid, evt_previd
-        );
+        let url = self.get_index_url(&[
+            ("title", &page),
+            ("type", "revision"),
+            ("diff", &evt_curid.to_string()),
+            ("oldid", &evt_previd.to_string()),
+        ]);
 
         let msg = format!(
             r#"[log/patrol] <a href="{}">{}</a> marked <a href="{}">revision {}</a>{} of <a href="{}">{}</a> patrolled"#,
@@ -788,7 +1991,7 @@ impl MediaWikiEmitter {
             page
         );
 
-        self.configured_api.emit(msg, true);
+        self.emit(evt, msg, true);
     }
 
     fn handle_evt_log_profile(&self, evt: &json::JsonValue) {
@@ -799,10 +2002,115 @@ impl MediaWikiEmitter {
             r#"[log/profile] <a href="{}">{}</a> {}"#,
             self.get_user_url(&user),
             user,
-            comment
+            self.wikitext.render(&comment, &evt["title"].to_string())
+        );
+
+        self.emit(evt, msg, true);
+    }
+
+    // Render protection details as e.g. "edit=sysop (indefinite), move=sysop (expires ...)".
+    // Falls back to the preformatted description of older MediaWiki versions.
+    fn explain_protection(log_params: &json::JsonValue) -> String {
+        if !log_params["details"].is_array() {
+            return MediaWikiEmitter::log_param(log_params, "description").unwrap_or_default();
+        }
+
+        log_params["details"]
+            .members()
+            .map(|detail| {
+                let expiry = MediaWikiEmitter::log_param(detail, "expiry");
+
+                format!(
+                    "{}={} ({}){}",
+                    MediaWikiEmitter::log_param(detail, "type").unwrap_or_default(),
+                    MediaWikiEmitter::log_param(detail, "level").unwrap_or_default(),
+                    match expiry.as_deref() {
+                        None | Some("infinite") | Some("infinity") => "indefinite".to_string(),
+                        Some(expiry) => format!("expires {}", expiry),
+                    },
+                    MediaWikiEmitter::cond_string(detail["cascade"] == true, " [cascading]", "")
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    fn handle_evt_log_protect(&self, evt: &json::JsonValue) {
+        let log_action = evt["log_action"].to_string();
+
+        let user = evt["user"].to_string();
+        let page = evt["title"].to_string();
+        let comment = evt["comment"].to_string();
+
+        let log_params = &evt["log_params"];
+
+        let msg = match &*log_action {
+            "unprotect" => format!(
+                r#"[log/protect] <a href="{}">{}</a> removed protection from <a href="{}">{}</a> {}"#,
+                self.get_user_url(&user),
+                user,
+                self.get_url(&page),
+                page,
+                self.explain_comment(&comment, &page)
+            ),
+            "move_prot" => format!(
+                r#"[log/protect] <a href="{}">{}</a> moved protection settings{} to <a href="{}">{}</a>"#,
+                self.get_user_url(&user),
+                user,
+                match log_params["oldtitle_title"].as_str() {
+                    Some(evt_oldtitle) => format!(
+                        r#" from <a href="{}">{}</a>"#,
+                        self.get_url(evt_oldtitle),
+                        htmlescape_str(evt_oldtitle)
+                    ),
+                    None => String::new(),
+                },
+                self.get_url(&page),
+                page
+            ),
+            _ => format!(
+                r#"[log/protect] <a href="{}">{}</a> {} <a href="{}">{}</a>: {} {}"#,
+                self.get_user_url(&user),
+                user,
+                MediaWikiEmitter::cond_string(
+                    log_action == "modify",
+                    "changed protection of",
+                    "protected",
+                ),
+                self.get_url(&page),
+                page,
+                MediaWikiEmitter::explain_protection(log_params),
+                self.explain_comment(&comment, &page)
+            )
+        };
+
+        self.emit(evt, msg, true);
+    }
+
+    fn handle_evt_log_renameuser(&self, evt: &json::JsonValue) {
+        let user = evt["user"].to_string();
+
+        let log_params = &evt["log_params"];
+
+        let msg = format!(
+            r#"[log/renameuser] <a href="{}">{}</a> renamed {}{}{}"#,
+            self.get_user_url(&user),
+            user,
+            MediaWikiEmitter::log_param(log_params, "olduser").unwrap_or_else(|| "a user".to_string()),
+            match log_params["newuser"].as_str() {
+                Some(evt_newuser) => format!(
+                    r#" to <a href="{}">{}</a>"#,
+                    self.get_user_url(evt_newuser),
+                    htmlescape_str(evt_newuser)
+                ),
+                None => String::new(),
+            },
+            MediaWikiEmitter::log_param(log_params, "edits")
+                .map(|edits| format!(" ({} edits)", edits))
+                .unwrap_or_default()
         );
 
-        self.configured_api.emit(msg, true);
+        self.emit(evt, msg, true);
     }
 
     fn handle_evt_log_rights(&self, evt: &json::JsonValue) {
@@ -813,10 +2121,10 @@ impl MediaWikiEmitter {
             r#"[log/rights] <a href="{}">{}</a> {}"#,
             self.get_user_url(&user),
             user,
-            comment
+            self.wikitext.render(&comment, &evt["title"].to_string())
         );
 
-        self.configured_api.emit(msg, true);
+        self.emit(evt, msg, true);
     }
 
     fn handle_evt_log_thanks(&self, evt: &json::JsonValue) {
@@ -824,25 +2132,89 @@ impl MediaWikiEmitter {
 
         let msg = format!(
             "[log/thanks] {}",
-            comment
+            self.wikitext.render(&comment, &evt["title"].to_string())
         );
 
-        self.configured_api.emit(msg, true);
+        self.emit(evt, msg, true);
+    }
+
+    // 1234567 -> "1.2 MB"
+    fn format_file_size(bytes: u64) -> String {
+        match bytes {
+            bytes if bytes >= 1 << 20 => format!("{:.1} MB", bytes as f64 / f64::from(1 << 20)),
+            bytes if bytes >= 1 << 10 => format!("{:.1} KB", bytes as f64 / f64::from(1 << 10)),
+            bytes => format!("{} bytes", bytes),
+        }
+    }
+
+    // e.g. " (1.2 MB, 800×600, image/png)"
+    fn explain_image_info(image_info: &ImageInfo) -> String {
+        let mut details = vec![MediaWikiEmitter::format_file_size(image_info.size)];
+
+        if image_info.width > 0 && image_info.height > 0 {
+            details.push(format!("{}×{}", image_info.width, image_info.height));
+        }
+
+        if !image_info.mime.is_empty() {
+            details.push(image_info.mime.clone());
+        }
+
+        format!(" ({})", details.join(", "))
     }
 
     fn handle_evt_log_upload(&self, evt: &json::JsonValue) {
         let user = evt["user"].to_string();
         let file = evt["title"].to_string();
 
+        let (tag, verb) = match evt["log_action"].as_str() {
+            Some("overwrite") => ("[log/upload/overwrite]", "uploaded a new version of"),
+            Some("revert") => ("[log/upload/revert]", "reverted"),
+            _ => ("[log/upload]", "uploaded"),
+        };
+
+        let watchers = self.watchers(evt);
+
+        // digested uploads don't need their metadata
+        if watchers.is_empty() && self.digest(evt) {
+            return;
+        }
+
+        let thumb_width = match self.upload_previews {
+            true => Some(self.upload_preview_width),
+            false => None,
+        };
+
+        let image_info = self.wiki_api.get_image_info(&file, thumb_width);
+
+        // the upload comment doubles as initial page text, prefer the
+        // one stored with the file version
+        let comment = match image_info {
+            Some(ref image_info) if !image_info.comment.is_empty() => image_info.comment.clone(),
+            _ => evt["comment"].to_string(),
+        };
+
         let msg = format!(
-            r#"[log/upload] <a href="{}">{}</a> uploaded file: <a href="{}">{}</a>"#,
+            r#"{} <a href="{}">{}</a> {} <a href="{}">{}</a>{} {}{}"#,
+            tag,
             self.get_user_url(&user),
             user,
+            verb,
             self.get_url(&file),
-            file
+            file,
+            image_info.as_ref().map(MediaWikiEmitter::explain_image_info).unwrap_or_default(),
+            self.explain_comment(&comment, &file),
+            match image_info {
+                Some(ref image_info) if !image_info.url.is_empty() =>
+                    format!(r#" [<a href="{}">original</a>]"#, htmlescape_str(image_info.url.as_str())),
+                _ => String::new(),
+            }
         );
 
-        self.configured_api.emit(msg, true);
+        let thumb_url = image_info
+            .as_ref()
+            .and_then(|image_info| image_info.thumb_url.as_deref());
+
+        self.post(evt, watchers, msg, thumb_url, true, None);
     }
 
     fn handle_evt_log_usermerge(&self, evt: &json::JsonValue) {
@@ -852,10 +2224,13 @@ impl MediaWikiEmitter {
             r#"[log/usermerge] <a href="{}">{}</a> {}"#,
             self.get_user_url(&user),
             user,
-            MediaWikiEmitter::urldecode(&evt["log_action_comment"].to_string()),
+            self.wikitext.render(
+                &MediaWikiEmitter::urldecode(&evt["log_action_comment"].to_string()),
+                &evt["title"].to_string()
+            ),
         );
 
-        self.configured_api.emit(msg, true);
+        self.emit(evt, msg, true);
     }
 }
 
@@ -865,18 +2240,87 @@ impl MediaWikiEmitter {
 
 struct GithubEmitter {
     configured_api: ConfiguredApi,
+    config: GithubConfig,
 }
 
 impl GithubEmitter {
-    fn new() -> GithubEmitter {
-        let configured_api = ConfiguredApi::new(&"<b>GitHub</b>", telegram_bot::types::ParseMode::Html);
+    fn new(config: &GithubConfig, control: Arc<Control>) -> GithubEmitter {
+        let configured_api = ConfiguredApi::new(
+            &"<b>GitHub</b>",
+            telegram_bot::types::ParseMode::Html,
+            control,
+            Some(Source::GitHub),
+        );
 
         GithubEmitter {
-            configured_api
+            configured_api,
+            config: config.clone(),
+        }
+    }
+
+    // "• <a href="…">1a2b3c4</a> author: first line of the message"
+    fn explain_commit(url: &str, id: &str, author: &str, message: &str) -> String {
+        format!(
+            r#"• <a href="{}">{}</a> {}: {}"#,
+            url,
+            id.chars().take(7).collect::<String>(),
+            htmlescape_str(author),
+            // Commit messages can potentially trip up the Telegram
+            // HTML parser. That is, encode the string ensuring Git
+            // meta data is not incorrectly detected as html and
+            // therefore marked as invalid markup. i.e. "<foo@bar.com>"
+            htmlescape_str(message.lines().next().unwrap_or(""))
+        )
+    }
+
+    // The first commits of a push, one per line
+    fn explain_commits(&self, mut lines: Vec<String>) -> String {
+        let commits = lines.len();
+
+        lines.truncate(self.config.push_commits);
+
+        if commits > lines.len() {
+            lines.push(format!("… and {} more", commits - lines.len()));
+        }
+
+        match lines.is_empty() {
+            true => String::new(),
+            false => format!(":\n{}", lines.join("\n")),
         }
     }
 
+    // Issues and pull-requests share their numbering within a repository
+    fn thread_key(repository: &str, number: i64) -> String {
+        format!("github:{}#{}", repository, number)
+    }
+
+    // Stream, user and repository of an event, for the digest
+    fn digest_entry<'a>(delivery: &'a Delivery) -> Option<(&'static str, &'a str, &'a str)> {
+        let entry: (&'static str, &str, &str) = match delivery.payload {
+            afterparty::Event::Watch { ref sender, ref repository, .. } => ("star", &sender.login, &repository.full_name),
+            afterparty::Event::Fork { ref sender, ref repository, .. } => ("fork", &sender.login, &repository.full_name),
+            afterparty::Event::Push { ref sender, ref repository, .. } => ("push", &sender.login, &repository.full_name),
+            afterparty::Event::CommitComment { ref sender, ref repository, .. } => ("commit_comment", &sender.login, &repository.full_name),
+            afterparty::Event::IssueComment { ref sender, ref repository, .. } => ("issue_comment", &sender.login, &repository.full_name),
+            afterparty::Event::Issues { ref sender, ref repository, .. } => ("issues", &sender.login, &repository.full_name),
+            afterparty::Event::PullRequestReview { ref sender, ref repository, .. } => ("pull_request_review", &sender.login, &repository.full_name),
+            afterparty::Event::Release { ref sender, ref repository, .. } => ("release", &sender.login, &repository.full_name),
+            afterparty::Event::Delete { ref sender, ref repository, .. } => ("delete", &sender.login, &repository.full_name),
+            afterparty::Event::Member { ref sender, ref repository, .. } => ("member", &sender.login, &repository.full_name),
+            afterparty::Event::Repository { ref sender, ref repository, .. } => ("repository", &sender.login, &repository.full_name),
+            _ => return None,
+        };
+
+        Some(entry)
+    }
+
     fn handle_evt(&self, delivery: &Delivery) {
+        if let Some((kind, user, repository)) = GithubEmitter::digest_entry(delivery) {
+            if self.configured_api.digest(kind, user, repository) {
+                return;
+            }
+        }
+
         match delivery.payload {
             afterparty::Event::Watch { ref sender, ref repository, .. } => {
                 self.configured_api.emit(format!(
@@ -901,37 +2345,14 @@ impl GithubEmitter {
                     ),
                 ), true);
             }
-            afterparty::Event::PullRequest { ref sender, ref action, ref repository, ref pull_request, .. } => {
-                // "synchronize" events are less than useless
-                if action == "synchronize" {
-                    return;
-                }
-
-                self.configured_api.emit(format!(
-                    r#"<a href="{}">{}</a> {} pull-request <a href="{}">"{}" (#{})</a> to <a href="{}">{}</a> [<a href="{}">{} commits</a>; <a href="{}">{} changed files (+{}/-{})]</a>; <a href="{}">raw diff</a>]"#,
-                    &sender.html_url,
-                    sender.login,
-                    action,
-                    &pull_request.html_url,
-                    pull_request.title,
-                    pull_request.number,
-                    &repository.html_url,
-                    repository.full_name,
-                    &format!("{}/commits", &pull_request.html_url),
-                    pull_request.commits,
-                    &format!("{}/files", &pull_request.html_url),
-                    pull_request.changed_files,
-                    pull_request.additions,
-                    pull_request.deletions,
-                    &pull_request.diff_url,
-                ), false);
-            }
             afterparty::Event::PullRequestReview { ref sender, ref action, ref repository, ref pull_request, ref review, .. } => {
                 if review.state == "edited" {
                     return;
                 }
 
-                self.configured_api.emit(format!(
+                let thread_key = GithubEmitter::thread_key(&repository.full_name, pull_request.number);
+
+                self.configured_api.emit_threaded(&thread_key, format!(
                     r#"<a href="{}">{}</a> {} <a href="{}">{}</a> pull-request <a href="{}">"{}" ({}/#{})</a> [<a href="{}">commits</a>; <a href="{}">changed files</a>; <a href="{}">raw diff</a>]"#,
                     &sender.html_url,
                     sender.login,
@@ -1002,7 +2423,9 @@ impl GithubEmitter {
                 ), false);
             }
             afterparty::Event::IssueComment { ref sender, ref action, ref comment, ref issue, ref repository } => {
-                self.configured_api.emit(format!(
+                let thread_key = GithubEmitter::thread_key(&repository.full_name, issue.number);
+
+                self.configured_api.emit_threaded(&thread_key, format!(
                     r#"<a href="{}">{}</a> {} a comment on issue <a href="{}">{}</a> ({:?})"#,
                     &sender.html_url,
                     sender.login,
@@ -1019,7 +2442,9 @@ impl GithubEmitter {
                 ), true);
             }
             afterparty::Event::Issues { ref sender, ref action, ref issue, ref repository, .. } => {
-                self.configured_api.emit(format!(
+                let thread_key = GithubEmitter::thread_key(&repository.full_name, issue.number);
+
+                self.configured_api.emit_threaded(&thread_key, format!(
                     r#"<a href="{}">{}</a> {} issue <a href="{}">{}</a> ({:?})"#,
                     &sender.html_url,
                     sender.login,
@@ -1075,7 +2500,62 @@ impl GithubEmitter {
                     sender.login,
                 ), false);
             }
-            afterparty::Event::Push { ref forced, ref sender, ref commits, ref compare, ref repository, ref _ref, .. } => {
+            afterparty::Event::Push { ref created, ref deleted, ref forced, ref sender, ref commits, ref compare, ref repository, ref _ref, .. } => {
+                // deleted refs are announced by the delete event
+                if *deleted {
+                    return;
+                }
+
+                let commit_lines = commits
+                    .iter()
+                    .map(|commit| GithubEmitter::explain_commit(
+                        &commit.url,
+                        &commit.id,
+                        commit.author.username.as_deref().or(commit.author.name.as_deref()).unwrap_or(""),
+                        &commit.message,
+                    ))
+                    .collect::<Vec<String>>();
+
+                // created branches are announced here, created tags
+                // without new commits by the create event
+                if *created && commits.is_empty() && _ref.starts_with("refs/tags/") {
+                    return;
+                }
+
+                if let Some(tag) = _ref.strip_prefix("refs/tags/") {
+                    if !self.config.tag_pushes {
+                        return;
+                    }
+
+                    return self.configured_api.emit(format!(
+                        r#"<a href="{}">{}</a> {}pushed tag <a href="{}/tree/{}">{}</a> to <a href="{}">{}</a>"#,
+                        &sender.html_url,
+                        sender.login,
+                        { if *forced { "force-" } else { "" } },
+                        &repository.html_url,
+                        tag,
+                        htmlescape_str(tag),
+                        &repository.html_url,
+                        repository.full_name,
+                    ), false);
+                }
+
+                let branch = _ref.strip_prefix("refs/heads/").unwrap_or(_ref);
+
+                if *created {
+                    return self.configured_api.emit(format!(
+                        r#"<a href="{}">{}</a> created branch <a href="{}/tree/{}">{}</a> in <a href="{}">{}</a>{}"#,
+                        &sender.html_url,
+                        sender.login,
+                        &repository.html_url,
+                        branch,
+                        htmlescape_str(branch),
+                        &repository.html_url,
+                        repository.full_name,
+                        self.explain_commits(commit_lines),
+                    ), false);
+                }
+
                 self.configured_api.emit(format!(
                     r#"<a href="{}">{}</a> {}pushed <a href="{}">{} commit{}</a> to <a href="{}">{}</a> ({}){}"#,
                     &sender.html_url,
@@ -1086,23 +2566,8 @@ impl GithubEmitter {
                     { if commits.len() == 1 { "" } else { "s" } },
                     &repository.html_url,
                     repository.full_name,
-                    _ref,
-                    {
-                        if commits.len() == 1 {
-                            format!(
-                                ": {}",
-
-                                // This can potentially trip up the Telegram
-                                // HTML parser. That is, encode the string
-                                // ensuring Git meta data is not incorrectly
-                                // detected as html and thereforce marked
-                                // as invalid markup. i.e. "<foo@bar.com>"
-                                htmlescape_str(commits[0].message.clone())
-                            )
-                        } else {
-                            "".to_string()
-                        }
-                    },
+                    htmlescape_str(branch),
+                    self.explain_commits(commit_lines),
                 ), true);
             }
             afterparty::Event::Repository { ref sender, ref action, ref repository, .. } => {
@@ -1115,11 +2580,435 @@ impl GithubEmitter {
                     repository.full_name,
                 ), false);
             }
+            _ => match json::parse(delivery.unparsed_payload) {
+                Ok(payload) => self.handle_raw_evt(delivery.event, &payload),
+                Err(err) => eprintln!("Failed to parse {} event: {}", delivery.event, err),
+            },
+        }
+    }
+
+    // "failed", "timed out"
+    fn explain_conclusion(conclusion: &str) -> String {
+        match conclusion {
+            "failure" => "failed".to_string(),
+            "error" => "errored".to_string(),
+            conclusion => conclusion.replace('_', " "),
+        }
+    }
+
+    // "labels: bug, ui" and "reviewers: alice, core-team" lines
+    fn explain_pull_request_details(pull_request: &json::JsonValue) -> String {
+        let labels = pull_request["labels"]
+            .members()
+            .filter_map(|label| label["name"].as_str())
+            .map(htmlescape_str)
+            .collect::<Vec<String>>();
+
+        let reviewers = pull_request["requested_reviewers"]
+            .members()
+            .filter_map(|reviewer| reviewer["login"].as_str())
+            .chain(pull_request["requested_teams"].members().filter_map(|team| team["name"].as_str()))
+            .map(htmlescape_str)
+            .collect::<Vec<String>>();
+
+        let mut details = String::new();
+
+        if !labels.is_empty() {
+            details.push_str(&format!("\nlabels: {}", labels.join(", ")));
+        }
+
+        if !reviewers.is_empty() {
+            details.push_str(&format!("\nreviewers: {}", reviewers.join(", ")));
+        }
+
+        details
+    }
+
+    // afterparty expects the merging user as a string and an organization,
+    // failing on most pull-request payloads, which are therefore read raw
+    fn handle_pull_request_evt(&self, payload: &json::JsonValue, user: &str, repo: &str) {
+        let action = payload["action"].as_str().unwrap_or("");
+        let pull_request = &payload["pull_request"];
+        let repository = &payload["repository"];
+
+        let is_noisy = GITHUB_NOISY_PR_ACTIONS.contains(&action);
+
+        if is_noisy && !self.config.pull_request_actions.iter().any(|configured| configured == action) {
+            return;
+        }
+
+        let base = pull_request["base"]["ref"].as_str().unwrap_or("?");
+
+        let name_of = |value: &json::JsonValue| {
+            htmlescape_str(value["login"].as_str().or_else(|| value["name"].as_str()).unwrap_or("?"))
+        };
+
+        // reviews are requested from either a user or a team
+        let requested = match payload["requested_reviewer"].is_null() {
+            true => name_of(&payload["requested_team"]),
+            false => name_of(&payload["requested_reviewer"]),
+        };
+
+        // what is done to the pull-request, and what follows it
+        let (verb, target) = match action {
+            "closed" if pull_request["merged"].as_bool().unwrap_or(false) => {
+                let merged_by = &pull_request["merged_by"];
+
+                (
+                    "merged",
+                    format!(
+                        r#" into <a href="{}/tree/{}">{}</a> of {}{}"#,
+                        repository["html_url"],
+                        base,
+                        htmlescape_str(base),
+                        repo,
+                        // merged by someone else than the sender, e.g. through auto-merge
+                        match merged_by["login"].as_str() {
+                            Some(login) if payload["sender"]["login"] != login => format!(
+                                r#" (merged by <a href="{}">{}</a>)"#,
+                                merged_by["html_url"],
+                                htmlescape_str(login)
+                            ),
+                            _ => String::new(),
+                        }
+                    ),
+                )
+            }
+            "closed" => ("closed", format!(" in {} without merging", repo)),
+            "ready_for_review" => ("marked", format!(" in {} as ready for review", repo)),
+            "converted_to_draft" => ("converted", format!(" in {} to a draft", repo)),
+            "labeled" | "unlabeled" => (
+                action,
+                format!(" in {} with {}", repo, htmlescape_str(payload["label"]["name"].as_str().unwrap_or("?"))),
+            ),
+            "assigned" | "unassigned" => (
+                action,
+                format!(" in {} to {}", repo, name_of(&payload["assignee"])),
+            ),
+            "review_requested" => (
+                "requested a review of",
+                format!(" in {} from {}", repo, requested),
+            ),
+            "review_request_removed" => (
+                "withdrew the review request of",
+                format!(" in {} from {}", repo, requested),
+            ),
+            _ => (action, format!(" to {}", repo)),
+        };
+
+        let html_url = pull_request["html_url"].as_str().unwrap_or("");
+        let number = pull_request["number"].as_i64().unwrap_or(0);
+
+        let thread_key = GithubEmitter::thread_key(repository["full_name"].as_str().unwrap_or(""), number);
+
+        self.configured_api.emit_threaded(&thread_key, format!(
+            r#"{} {} pull-request <a href="{}">"{}" (#{})</a>{}{} [<a href="{}/commits">{} commits</a>; <a href="{}/files">{} changed files (+{}/-{})</a>; <a href="{}">raw diff</a>]{}"#,
+            user,
+            verb,
+            html_url,
+            htmlescape_str(pull_request["title"].as_str().unwrap_or("")),
+            number,
+            // converted ones just became drafts, as the target says
+            match (pull_request["draft"].as_bool(), action) {
+                (Some(true), "converted_to_draft") => "",
+                (Some(true), _) => " (draft)",
+                _ => "",
+            },
+            target,
+            html_url,
+            pull_request["commits"],
+            html_url,
+            pull_request["changed_files"],
+            pull_request["additions"],
+            pull_request["deletions"],
+            pull_request["diff_url"],
+            // labels and reviewers are of interest when a pull-request opens up
+            match action {
+                "opened" | "reopened" | "ready_for_review" => GithubEmitter::explain_pull_request_details(pull_request),
+                _ => String::new(),
+            },
+        ), false);
+    }
+
+    // Events afterparty has no usable variant for, from the raw payload
+    fn handle_raw_evt(&self, event: &str, payload: &json::JsonValue) {
+        let sender = &payload["sender"];
+        let repository = &payload["repository"];
+
+        let sender_login = sender["login"].as_str().unwrap_or("");
+        let repository_name = repository["full_name"].as_str().unwrap_or("");
+
+        if self.configured_api.digest(event, sender_login, repository_name) {
+            return;
+        }
+
+        let user = format!(r#"<a href="{}">{}</a>"#, sender["html_url"], htmlescape_str(sender_login));
+        let repo = format!(r#"<a href="{}">{}</a>"#, repository["html_url"], repository_name);
+
+        let default_branch = repository["default_branch"].as_str().unwrap_or("master");
+
+        // CI runs only matter when they fail on the default branch
+        let ci_failure = |name: &str, conclusion: &str, branch: &str, sha: &str, url: &str| {
+            if !matches!(conclusion, "failure" | "error" | "timed_out") || branch != default_branch {
+                return None;
+            }
+
+            Some(format!(
+                r#"<a href="{}">{}</a> {} on {} ({} at <a href="{}/commit/{}">{}</a>)"#,
+                url,
+                htmlescape_str(name),
+                GithubEmitter::explain_conclusion(conclusion),
+                repo,
+                htmlescape_str(branch),
+                repository["html_url"],
+                sha,
+                sha.chars().take(7).collect::<String>(),
+            ))
+        };
+
+        match event {
+            "pull_request" => self.handle_pull_request_evt(payload, &user, &repo),
+            "create" => {
+                let ref_name = payload["ref"].as_str().unwrap_or("");
+                let ref_type = payload["ref_type"].as_str().unwrap_or("");
+
+                // branches are announced by the push creating them
+                if ref_type == "branch" || (ref_type == "tag" && !self.config.tag_pushes) {
+                    return;
+                }
+
+                self.configured_api.emit(format!(
+                    r#"{} created {} <a href="{}/tree/{}">{}</a> in {}"#,
+                    user,
+                    ref_type,
+                    repository["html_url"],
+                    ref_name,
+                    htmlescape_str(ref_name),
+                    repo,
+                ), false);
+            }
+            "gollum" => {
+                let pages = payload["pages"]
+                    .members()
+                    .map(|page| format!(
+                        r#"<a href="{}">{}</a> ({})"#,
+                        page["html_url"],
+                        htmlescape_str(page["title"].as_str().unwrap_or("?")),
+                        page["action"],
+                    ))
+                    .collect::<Vec<String>>();
+
+                if pages.is_empty() {
+                    return;
+                }
+
+                self.configured_api.emit(format!(
+                    "{} edited the wiki of {}: {}",
+                    user,
+                    repo,
+                    pages.join(", "),
+                ), false);
+            }
+            "status" => {
+                let state = payload["state"].as_str().unwrap_or("");
+
+                let is_on_default_branch = payload["branches"]
+                    .members()
+                    .any(|branch| branch["name"] == default_branch);
+
+                if !is_on_default_branch {
+                    return;
+                }
+
+                let msg = ci_failure(
+                    payload["context"].as_str().unwrap_or("status"),
+                    state,
+                    default_branch,
+                    payload["sha"].as_str().unwrap_or(""),
+                    payload["target_url"].as_str().unwrap_or(""),
+                );
+
+                if let Some(msg) = msg {
+                    self.configured_api.emit(match payload["description"].as_str() {
+                        Some(description) if !description.is_empty() =>
+                            format!("{}: {}", msg, htmlescape_str(description)),
+                        _ => msg,
+                    }, true);
+                }
+            }
+            "check_run" => {
+                let check_run = &payload["check_run"];
+
+                // GitHub Actions jobs are covered by their workflow run
+                if payload["action"] != "completed" || check_run["app"]["slug"] == "github-actions" {
+                    return;
+                }
+
+                let msg = ci_failure(
+                    check_run["name"].as_str().unwrap_or("check"),
+                    check_run["conclusion"].as_str().unwrap_or(""),
+                    check_run["check_suite"]["head_branch"].as_str().unwrap_or(""),
+                    check_run["head_sha"].as_str().unwrap_or(""),
+                    check_run["html_url"].as_str().unwrap_or(""),
+                );
+
+                if let Some(msg) = msg {
+                    self.configured_api.emit(msg, true);
+                }
+            }
+            "workflow_run" => {
+                let workflow_run = &payload["workflow_run"];
+
+                if payload["action"] != "completed" {
+                    return;
+                }
+
+                let msg = ci_failure(
+                    workflow_run["name"].as_str().unwrap_or("workflow"),
+                    workflow_run["conclusion"].as_str().unwrap_or(""),
+                    workflow_run["head_branch"].as_str().unwrap_or(""),
+                    workflow_run["head_sha"].as_str().unwrap_or(""),
+                    workflow_run["html_url"].as_str().unwrap_or(""),
+                );
+
+                if let Some(msg) = msg {
+                    self.configured_api.emit(msg, true);
+                }
+            }
+            "deployment" => {
+                let deployment = &payload["deployment"];
+
+                self.configured_api.emit(format!(
+                    "{} started a deployment of {} to {} from {}",
+                    user,
+                    htmlescape_str(deployment["ref"].as_str().unwrap_or("?")),
+                    htmlescape_str(deployment["environment"].as_str().unwrap_or("?")),
+                    repo,
+                ), false);
+            }
+            "deployment_status" => {
+                let deployment = &payload["deployment"];
+                let deployment_status = &payload["deployment_status"];
+
+                let state = deployment_status["state"].as_str().unwrap_or("");
+
+                // intermediate states
+                if matches!(state, "pending" | "queued" | "in_progress" | "inactive") {
+                    return;
+                }
+
+                let url = deployment_status["log_url"]
+                    .as_str()
+                    .or_else(|| deployment_status["target_url"].as_str())
+                    .unwrap_or("");
+
+                self.configured_api.emit(format!(
+                    r#"Deployment of {} to {} from {}: <a href="{}">{}</a>"#,
+                    htmlescape_str(deployment["ref"].as_str().unwrap_or("?")),
+                    htmlescape_str(deployment_status["environment"].as_str().unwrap_or("?")),
+                    repo,
+                    url,
+                    state,
+                ), state != "success");
+            }
+            "discussion" => {
+                let discussion = &payload["discussion"];
+                let action = payload["action"].as_str().unwrap_or("");
+
+                let verb = match action {
+                    "created" => "started",
+                    "answered" => "marked an answer in",
+                    "closed" | "reopened" | "deleted" => action,
+                    _ => return,
+                };
+
+                let thread_key = GithubEmitter::thread_key(
+                    repository_name,
+                    discussion["number"].as_i64().unwrap_or(0),
+                );
+
+                self.configured_api.emit_threaded(&thread_key, format!(
+                    r#"{} {} discussion <a href="{}">{}#{}</a> ("{}")"#,
+                    user,
+                    verb,
+                    discussion["html_url"],
+                    repository_name,
+                    discussion["number"],
+                    htmlescape_str(discussion["title"].as_str().unwrap_or("")),
+                ), true);
+            }
+            "pull_request_review_comment" => {
+                let comment = &payload["comment"];
+                let pull_request = &payload["pull_request"];
+
+                if payload["action"] != "created" {
+                    return;
+                }
+
+                let thread_key = GithubEmitter::thread_key(
+                    repository_name,
+                    pull_request["number"].as_i64().unwrap_or(0),
+                );
+
+                self.configured_api.emit_threaded(&thread_key, format!(
+                    r#"{} commented on <a href="{}">{}</a> in pull-request <a href="{}">"{}" ({}/#{})</a>"#,
+                    user,
+                    comment["html_url"],
+                    htmlescape_str(comment["path"].as_str().unwrap_or("?")),
+                    pull_request["html_url"],
+                    htmlescape_str(pull_request["title"].as_str().unwrap_or("")),
+                    repository_name,
+                    pull_request["number"],
+                ), false);
+            }
             _ => (),
         }
     }
 }
 
+// Hands deliveries to a fresh emitter, including events afterparty
+// fails to parse, which its hub would silently drop
+struct GithubHook {
+    config: GithubConfig,
+    control: Arc<Control>,
+}
+
+impl hyper::server::Handler for GithubHook {
+    fn handle(&self, mut req: hyper::server::Request, res: hyper::server::Response) {
+        let header = |name: &str| {
+            req.headers
+                .get_raw(name)
+                .and_then(|values| values.first())
+                .map(|value| String::from_utf8_lossy(value).into_owned())
+        };
+
+        let (event, id) = match (header("X-Github-Event"), header("X-Github-Delivery")) {
+            (Some(event), Some(id)) => (event, id),
+            _ => {
+                let _ = res.send(b"ok");
+
+                return;
+            }
+        };
+
+        let mut payload = String::new();
+
+        if req.read_to_string(&mut payload).is_ok() {
+            let emitter = GithubEmitter::new(&self.config, self.control.clone());
+
+            match Delivery::new(&id, &event, &payload, None) {
+                Some(delivery) => emitter.handle_evt(&delivery),
+                None => match json::parse(&payload) {
+                    Ok(payload) => emitter.handle_raw_evt(&event, &payload),
+                    Err(err) => eprintln!("Failed to parse {} event: {}", event, err),
+                },
+            }
+        }
+
+        let _ = res.send(b"ok");
+    }
+}
+
 /*
  * JIRA CHANGE EVENTS
  */
@@ -1184,8 +3073,13 @@ struct JiraEmitter {
 }
 
 impl JiraEmitter {
-    fn new() -> JiraEmitter {
-        let configured_api = ConfiguredApi::new(&"<b>Jira</b>", telegram_bot::types::ParseMode::Html);
+    fn new(control: Arc<Control>) -> JiraEmitter {
+        let configured_api = ConfiguredApi::new(
+            &"<b>Jira</b>",
+            telegram_bot::types::ParseMode::Html,
+            control,
+            Some(Source::Jira),
+        );
 
         JiraEmitter {
             configured_api
@@ -1200,6 +3094,12 @@ impl JiraEmitter {
             _ => { return; }
         };
 
+        let kind = event.webhook_event.trim_start_matches("jira:");
+
+        if self.configured_api.digest(kind, &event.user.display_name, &event.issue.key) {
+            return;
+        }
+
         self._handle_marked_evt(
             event,
             event_type,
@@ -1231,7 +3131,10 @@ impl JiraEmitter {
     }
 
     fn _handle_marked_evt(&self, event: JiraEvent, event_type: JiraEventTypes) {
-        self.configured_api.emit(
+        let thread_key = format!("jira:{}", event.issue.key);
+
+        self.configured_api.emit_threaded(
+            &thread_key,
             self._get_formatted_event(event, event_type),
             true,
         );
@@ -1280,8 +3183,13 @@ struct PayPalEmitter {
 }
 
 impl PayPalEmitter {
-    fn new() -> PayPalEmitter {
-        let configured_api = ConfiguredApi::new(&"<b>PayPal</b>", telegram_bot::types::ParseMode::Html);
+    fn new(control: Arc<Control>) -> PayPalEmitter {
+        let configured_api = ConfiguredApi::new(
+            &"<b>PayPal</b>",
+            telegram_bot::types::ParseMode::Html,
+            control,
+            Some(Source::PayPal),
+        );
 
         PayPalEmitter {
             configured_api
@@ -1289,8 +3197,18 @@ impl PayPalEmitter {
     }
 
     fn handle_evt(&self, event: &PayPalIPN) {
+        let msg = self._get_formatted_event(event);
+
+        self.configured_api.control.set_last_donation(msg.clone());
+
+        let donor = format!("{} {}", event.first_name, event.last_name);
+
+        if self.configured_api.digest("donation", &donor, &event.mc_currency) {
+            return;
+        }
+
         self.configured_api.emit(
-            self._get_formatted_event(event),
+            msg,
             true,
         );
     }
@@ -1319,64 +3237,222 @@ impl PayPalEmitter {
 
 struct EoP {
     thread_pool: scoped_threadpool::Pool,
+    config: Config,
+    control: Arc<Control>,
 }
 
 impl EoP {
     fn new() -> EoP {
+        let config = Config::load();
+        let control = Arc::new(Control::new(&config));
+
         EoP {
-            thread_pool: Pool::new(4)
+            thread_pool: Pool::new(7),
+            config,
+            control,
         }
     }
 
     fn init(&mut self) {
+        let config = &self.config;
+        let control = &self.control;
+
         self.thread_pool.scoped(|scoped| {
-            scoped.execute(|| {
-                EoP::init_mediawiki();
+            scoped.execute(move || {
+                EoP::init_mediawiki(config, control.clone());
+            });
+
+            scoped.execute(move || {
+                EoP::init_bot(config, control.clone());
+            });
+
+            scoped.execute(move || {
+                EoP::init_github(config, control.clone());
+            });
+
+            scoped.execute(move || {
+                EoP::init_jira(control.clone());
             });
 
-            scoped.execute(|| {
-                EoP::init_github();
+            scoped.execute(move || {
+                EoP::init_paypal(control.clone());
             });
 
-            scoped.execute(|| {
-                EoP::init_jira();
+            scoped.execute(move || {
+                EoP::init_digest(control.clone());
             });
 
-            scoped.execute(|| {
-                EoP::init_paypal();
+            scoped.execute(move || {
+                EoP::init_approval_report(config, control.clone());
             });
         });
     }
 
-    fn init_mediawiki() {
-        let emitter = MediaWikiEmitter::new();
+    fn init_mediawiki(config: &Config, control: Arc<Control>) {
+        let wikis = config.mediawiki.wikis();
 
-        let socket = UdpSocket::bind(MEDIAWIKI_ENDPOINT)
-            .expect("✘ MediaWikiEmitter failed to create socket");
+        let emitters = wikis
+            .iter()
+            .enumerate()
+            .map(|(wiki_idx, wiki)| MediaWikiEmitter::new(config, wiki, wiki_idx, control.clone()))
+            .collect::<Vec<MediaWikiEmitter>>();
 
-        println!("✔ MediaWikiEmitter online. ({})", MEDIAWIKI_ENDPOINT);
+        // wikis without a port of their own send to the default endpoint
+        let mut endpoints = wikis
+            .iter()
+            .map(|wiki| match wiki.port {
+                Some(port) => format!("0.0.0.0:{}", port),
+                None => MEDIAWIKI_ENDPOINT.to_string(),
+            })
+            .collect::<Vec<String>>();
 
-        let mut buf = [0; 2048];
-        loop {
-            match socket.recv_from(&mut buf) {
-                Ok((amt, _)) => {
-                    let instr = std::str::from_utf8(&buf[0..amt]).unwrap_or("");
+        endpoints.sort();
+        endpoints.dedup();
+
+        let (tx, rx) = mpsc::channel::<(json::JsonValue, u16, SocketAddr)>();
+
+        for endpoint in endpoints {
+            let socket = UdpSocket::bind(&endpoint)
+                .expect("✘ MediaWikiEmitter failed to create socket");
+
+            let port = socket.local_addr().map(|addr| addr.port()).unwrap_or(0);
+
+            println!("✔ MediaWikiEmitter online. ({})", endpoint);
+
+            let tx = tx.clone();
+
+            // The receive loop only parses datagrams and hands them off,
+            // so it never blocks on the MediaWiki or Telegram APIs.
+            thread::spawn(move || {
+                let mut buf = [0; 2048];
+                loop {
+                    match socket.recv_from(&mut buf) {
+                        Ok((amt, source)) => {
+                            let instr = std::str::from_utf8(&buf[0..amt]).unwrap_or("");
 
-                    if let Ok(evt) = json::parse(instr) {
-                        emitter.handle_evt(&evt);
+                            if let Ok(evt) = json::parse(instr) {
+                                let _ = tx.send((evt, port, source));
+                            }
+                        }
+                        Err(e) => println!("couldn't receive a datagram: {}", e),
+                    }
+                }
+            });
+        }
+
+        let batch_window = Duration::from_millis(config.mediawiki.batch_window_ms);
+
+        loop {
+            let received = match rx.recv_timeout(MEDIAWIKI_IDLE_TICK) {
+                Ok(received) => received,
+                // floods also end on quiet wikis
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    for emitter in &emitters {
+                        emitter.report_calmed_floods();
                     }
+
+                    continue;
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            };
+
+            let mut batch = vec![received];
+
+            let deadline = Instant::now() + batch_window;
+
+            while batch.len() < MEDIAWIKI_MAX_BATCH {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+
+                match rx.recv_timeout(remaining) {
+                    Ok(received) => batch.push(received),
+                    Err(_) => break,
+                }
+            }
+
+            let mut wiki_batches = emitters.iter().map(|_| Vec::new()).collect::<Vec<Vec<json::JsonValue>>>();
+
+            for (evt, port, source) in batch {
+                match emitters.iter().position(|emitter| emitter.accepts(&evt, port, &source)) {
+                    Some(wiki_idx) => wiki_batches[wiki_idx].push(evt),
+                    None => println!(
+                        "Dropping event from {} on port {}, no wiki matches server '{}'",
+                        source, port, evt["server_name"]
+                    ),
+                }
+            }
+
+            for (emitter, wiki_batch) in emitters.iter().zip(wiki_batches) {
+                if !wiki_batch.is_empty() {
+                    emitter.handle_batch(&wiki_batch);
                 }
-                Err(e) => println!("couldn't receive a datagram: {}", e),
             }
         }
     }
 
-    fn init_github() {
-        let mut hub = Hub::new();
+    fn init_approval_report(config: &Config, control: Arc<Control>) {
+        if !control.approvals().is_enabled() {
+            return;
+        }
+
+        let configured_api = ConfiguredApi::new(
+            "<b>Approvals</b>",
+            telegram_bot::types::ParseMode::Html,
+            control.clone(),
+            None,
+        );
+
+        let wikis = config.mediawiki.wikis();
+
+        loop {
+            if let Some(report) = control.approvals().take_due_report(&wikis) {
+                configured_api.emit(report, false);
+            }
+
+            thread::sleep(Duration::from_secs(60));
+        }
+    }
+
+    fn init_digest(control: Arc<Control>) {
+        if !control.digest().is_enabled() {
+            return;
+        }
+
+        let configured_api = ConfiguredApi::new(
+            "<b>Digest</b>",
+            telegram_bot::types::ParseMode::Html,
+            control.clone(),
+            None,
+        );
+
+        let channel_id = control.digest().channel_id().unwrap_or(configured_api.channel_id);
 
-        hub.handle("*", move |delivery: &Delivery| {
-            GithubEmitter::new().handle_evt(delivery);
-        });
+        println!("✔ Digest online.");
+
+        loop {
+            match control.digest().take_due() {
+                Some(msg) => configured_api.emit_to(channel_id, msg, false),
+                None => control.digest().flush(),
+            }
+
+            thread::sleep(Duration::from_secs(60));
+        }
+    }
+
+    fn init_bot(config: &Config, control: Arc<Control>) {
+        if !config.telegram.commands && !config.mediawiki.actions.enabled {
+            return;
+        }
+
+        println!("✔ Telegram bot online.");
+
+        Bot::new(config, control).run();
+    }
+
+    fn init_github(config: &Config, control: Arc<Control>) {
+        let hook = GithubHook {
+            config: config.github.clone(),
+            control,
+        };
 
         let srvc = match Server::http(GITHUB_ENDPOINT) {
             Ok(server) => {
@@ -1387,10 +3463,10 @@ impl EoP {
             Err(e) => panic!("✘ GithubEmitter failed to create socket: {}", e)
         };
 
-        let _ = srvc.handle(hub);
+        let _ = srvc.handle(hook);
     }
 
-    fn init_jira() {
+    fn init_jira(control: Arc<Control>) {
         let server = rouille::Server::new(
             JIRA_ENDPOINT,
             move |request| {
@@ -1414,7 +3490,7 @@ impl EoP {
                                 }
                             };
 
-                            JiraEmitter::new().handle_evt(data);
+                            JiraEmitter::new(control.clone()).handle_evt(data);
 
                             rouille::Response::json(&r#"{"ok":true}"#)
                         },
@@ -1437,7 +3513,7 @@ impl EoP {
         }
     }
 
-    fn init_paypal() {
+    fn init_paypal(control: Arc<Control>) {
         let server = rouille::Server::new(
             PAYPAL_ENDPOINT,
             move |request| {
@@ -1460,7 +3536,7 @@ impl EoP {
                                 Err(_) => return rouille::Response::json(&r#"{"ok":false}"#)
                             };
 
-                            PayPalEmitter::new().handle_evt(&data);
+                            PayPalEmitter::new(control.clone()).handle_evt(&data);
 
                             rouille::Response::json(&r#"{"ok":true}"#)
                         },
@@ -1491,3 +3567,52 @@ fn main() {
 
     eye.init();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approval(log_params: json::JsonValue) -> json::JsonValue {
+        json::object! {
+            "type" => "log",
+            "log_type" => "approval",
+            "log_action" => "approve",
+            "log_params" => log_params,
+        }
+    }
+
+    #[test]
+    fn parses_positional_approval_params() {
+        let evt = approval(json::array!["", "1234", 1200]);
+
+        assert_eq!(MediaWikiEmitter::approval_rev_ids(&evt), (Some(1234), Some(1200)));
+        assert_eq!(MediaWikiEmitter::unapproval_rev_id(&evt), Some(1200));
+    }
+
+    #[test]
+    fn parses_keyed_approval_params() {
+        let evt = approval(json::object! { "rev_id" => 1234, "old_rev_id" => "1200" });
+
+        assert_eq!(MediaWikiEmitter::approval_rev_ids(&evt), (Some(1234), Some(1200)));
+
+        let evt = approval(json::object! { "4::revid" => " 1234 ", "5::oldid" => 1200 });
+
+        assert_eq!(MediaWikiEmitter::approval_rev_ids(&evt), (Some(1234), Some(1200)));
+    }
+
+    #[test]
+    fn parses_serialized_approval_params() {
+        let evt = approval(r#"{"rev_id":"1234"}"#.into());
+
+        assert_eq!(MediaWikiEmitter::approval_rev_ids(&evt), (Some(1234), None));
+        assert_eq!(MediaWikiEmitter::unapproval_rev_id(&evt), Some(1234));
+    }
+
+    #[test]
+    fn tolerates_missing_approval_params() {
+        assert_eq!(MediaWikiEmitter::approval_rev_ids(&approval(json::JsonValue::Null)), (None, None));
+        assert_eq!(MediaWikiEmitter::approval_rev_ids(&approval("garbage".into())), (None, None));
+        assert_eq!(MediaWikiEmitter::approval_rev_ids(&approval(json::array![""])), (None, None));
+        assert_eq!(MediaWikiEmitter::approval_rev_ids(&approval(json::object! { "rev_id" => "n/a" })), (None, None));
+    }
+}
@@ -0,0 +1,71 @@
+/*
+ * TELEGRAM PHOTOS
+ *
+ * telegram-bot lacks sendPhoto. Photos given by url need no upload,
+ * so the request is a plain JSON one like sendMessage.
+ */
+
+use std::{borrow::Cow, ops::Not};
+
+use telegram_bot::types::requests::{
+    Error, HttpRequest, JsonIdResponse, JsonRequestType, Request, RequestType, RequestUrl,
+};
+use telegram_bot::{ChatRef, Message, MessageId, ParseMode, ToChatRef};
+
+// characters of a caption, after parsing its markup
+pub const MAX_CAPTION_LENGTH: usize = 1024;
+
+#[derive(Serialize)]
+pub struct SendPhoto<'s> {
+    chat_id: ChatRef,
+    photo: Cow<'s, str>,
+    caption: Cow<'s, str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parse_mode: Option<ParseMode>,
+    #[serde(skip_serializing_if = "Not::not")]
+    disable_notification: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply_to_message_id: Option<MessageId>,
+}
+
+impl<'s> Request for SendPhoto<'s> {
+    type Type = JsonRequestType<Self>;
+    type Response = JsonIdResponse<Message>;
+
+    fn serialize(&self) -> Result<HttpRequest, Error> {
+        Self::Type::serialize(RequestUrl::method("sendPhoto"), self)
+    }
+}
+
+impl<'s> SendPhoto<'s> {
+    pub fn new<C, P, T>(chat: C, photo_url: P, caption: T) -> Self
+    where
+        C: ToChatRef,
+        P: Into<Cow<'s, str>>,
+        T: Into<Cow<'s, str>>,
+    {
+        SendPhoto {
+            chat_id: chat.to_chat_ref(),
+            photo: photo_url.into(),
+            caption: caption.into(),
+            parse_mode: None,
+            disable_notification: false,
+            reply_to_message_id: None,
+        }
+    }
+
+    pub fn parse_mode(&mut self, parse_mode: ParseMode) -> &mut Self {
+        self.parse_mode = Some(parse_mode);
+        self
+    }
+
+    pub fn disable_notification(&mut self) -> &mut Self {
+        self.disable_notification = true;
+        self
+    }
+
+    pub fn reply_to(&mut self, message_id: MessageId) -> &mut Self {
+        self.reply_to_message_id = Some(message_id);
+        self
+    }
+}
@@ -0,0 +1,300 @@
+/*
+ * VANDALISM AND SPAM HEURISTICS
+ */
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+};
+
+use regex::Regex;
+
+use crate::anon::{self, Editor};
+use crate::config::RiskConfig;
+use crate::wiki_api::DiffLine;
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+pub enum RiskLevel {
+    Low,
+    Medium,
+    High,
+}
+
+pub struct RiskAssessment {
+    pub score: u32,
+    pub level: RiskLevel,
+    pub reasons: Vec<&'static str>,
+}
+
+impl RiskAssessment {
+    // e.g. "[<b>risk 5: high</b> (anonymous, blanking)] ", empty for low risk
+    pub fn badge(&self) -> String {
+        let level = match self.level {
+            RiskLevel::Low => return String::new(),
+            RiskLevel::Medium => "medium",
+            RiskLevel::High => "high",
+        };
+
+        format!("[<b>risk {}: {}</b> ({})] ", self.score, level, self.reasons.join(", "))
+    }
+}
+
+pub struct RiskScorer {
+    config: RiskConfig,
+    large_removal_percent: u32,
+    temp_account_prefix: String,
+    blacklist_rgx: Option<Regex>,
+    link_rgx: Regex,
+    // registration timestamps of recently created accounts
+    registrations: Mutex<HashMap<String, i64>>,
+}
+
+impl RiskScorer {
+    pub fn new(config: &RiskConfig, large_removal_percent: u32, temp_account_prefix: &str) -> RiskScorer {
+        let blacklist_rgx = match config.blacklist.is_empty() {
+            true => None,
+            false => {
+                let words = config.blacklist
+                    .iter()
+                    .map(|word| regex::escape(word))
+                    .collect::<Vec<String>>()
+                    .join("|");
+
+                Some(Regex::new(&format!(r"(?i)\b(?:{})\b", words)).unwrap())
+            }
+        };
+
+        RiskScorer {
+            config: config.clone(),
+            large_removal_percent,
+            temp_account_prefix: temp_account_prefix.to_string(),
+            blacklist_rgx,
+            link_rgx: Regex::new(r"(?i)https?://").unwrap(),
+            registrations: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    pub fn inspects_diff(&self) -> bool {
+        self.config.enabled && self.config.inspect_diff
+    }
+
+    fn new_account_window(&self) -> i64 {
+        self.config.new_account_hours as i64 * 3600
+    }
+
+    // Remember account creations from `newusers` log events, so later
+    // edits by the same account can be recognized as coming from a new user.
+    pub fn note_registration(&self, evt: &json::JsonValue) {
+        let timestamp = match evt["timestamp"].as_i64() {
+            Some(timestamp) => timestamp,
+            None => return,
+        };
+
+        // accounts created by others are logged against "User:<name>"
+        let title = evt["title"].to_string();
+        let user = match title.find(':') {
+            Some(idx) => title[idx + 1..].to_string(),
+            None => evt["user"].to_string(),
+        };
+
+        let window = self.new_account_window();
+
+        let mut registrations = self.registrations.lock().unwrap();
+
+        registrations.retain(|_, registered| timestamp - *registered < window);
+        registrations.insert(user, timestamp);
+    }
+
+    fn is_new_account(&self, user: &str, timestamp: i64) -> bool {
+        match self.registrations.lock().unwrap().get(user) {
+            Some(registered) => timestamp - registered < self.new_account_window(),
+            None => false,
+        }
+    }
+
+    fn is_large_removal(&self, evt: &json::JsonValue) -> bool {
+        match (evt["length"]["old"].as_i64(), evt["length"]["new"].as_i64()) {
+            (Some(len_old), Some(len_new)) if len_old > 0 && len_new < len_old =>
+                len_new == 0 || (len_old - len_new) * 100 / len_old > i64::from(self.large_removal_percent),
+            _ => false,
+        }
+    }
+
+    pub fn assess(&self, evt: &json::JsonValue, diff_lines: Option<&[DiffLine]>) -> RiskAssessment {
+        let mut score = 0;
+        let mut reasons = Vec::new();
+
+        let user = evt["user"].to_string();
+
+        // temporary accounts are just as anonymous as IP addresses
+        let is_anon = match anon::classify(&user, &self.temp_account_prefix) {
+            Editor::Registered => false,
+            Editor::Ip(_) | Editor::Temporary => true,
+        };
+
+        if is_anon {
+            score += self.config.anon_score;
+            reasons.push("anonymous");
+        } else if self.is_new_account(&user, evt["timestamp"].as_i64().unwrap_or(0)) {
+            score += self.config.new_account_score;
+            reasons.push("new account");
+        }
+
+        if self.is_large_removal(evt) {
+            score += self.config.blanking_score;
+            reasons.push("blanking");
+        }
+
+        let added = diff_lines
+            .unwrap_or(&[])
+            .iter()
+            .filter_map(|diff_line| match diff_line {
+                DiffLine::Added(text) => Some(text.as_str()),
+                DiffLine::Removed(_) => None,
+            })
+            .collect::<Vec<&str>>();
+
+        let removed_links = diff_lines
+            .unwrap_or(&[])
+            .iter()
+            .map(|diff_line| match diff_line {
+                DiffLine::Removed(text) => self.link_rgx.find_iter(text).count(),
+                DiffLine::Added(_) => 0,
+            })
+            .sum::<usize>();
+
+        let added_links = added
+            .iter()
+            .map(|text| self.link_rgx.find_iter(text).count())
+            .sum::<usize>();
+
+        if added_links > removed_links {
+            score += self.config.external_link_score;
+            reasons.push("external links");
+        }
+
+        if let Some(ref blacklist_rgx) = self.blacklist_rgx {
+            let comment = evt["comment"].to_string();
+            let title = evt["title"].to_string();
+
+            let has_blacklisted = blacklist_rgx.is_match(&comment)
+                || (evt["type"] == "new" && blacklist_rgx.is_match(&title))
+                || added.iter().any(|text| blacklist_rgx.is_match(text));
+
+            if has_blacklisted {
+                score += self.config.blacklist_score;
+                reasons.push("blacklisted words");
+            }
+        }
+
+        let level = if score >= self.config.high_threshold {
+            RiskLevel::High
+        } else if score >= self.config.medium_threshold {
+            RiskLevel::Medium
+        } else {
+            RiskLevel::Low
+        };
+
+        RiskAssessment {
+            score,
+            level,
+            reasons,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scorer(blacklist: &[&str]) -> RiskScorer {
+        let config = RiskConfig {
+            enabled: true,
+            blacklist: blacklist.iter().map(|word| word.to_string()).collect(),
+            ..RiskConfig::default()
+        };
+
+        RiskScorer::new(&config, 50, "~")
+    }
+
+    fn edit(user: &str, len_old: i64, len_new: i64) -> json::JsonValue {
+        json::object! {
+            "type" => "edit",
+            "user" => user,
+            "title" => "Page",
+            "comment" => "",
+            "timestamp" => 1000,
+            "length" => json::object! { "old" => len_old, "new" => len_new },
+        }
+    }
+
+    #[test]
+    fn registered_user_is_low_risk() {
+        let assessment = scorer(&[]).assess(&edit("Alice", 1000, 1100), None);
+
+        assert_eq!(assessment.score, 0);
+        assert!(assessment.level == RiskLevel::Low);
+        assert_eq!(assessment.badge(), "");
+    }
+
+    #[test]
+    fn anonymous_blanking_is_high_risk() {
+        let assessment = scorer(&[]).assess(&edit("203.0.113.7", 1000, 0), None);
+
+        assert_eq!(assessment.score, 5);
+        assert!(assessment.level == RiskLevel::High);
+        assert_eq!(assessment.reasons, vec!["anonymous", "blanking"]);
+    }
+
+    #[test]
+    fn temporary_accounts_count_as_anonymous() {
+        let assessment = scorer(&[]).assess(&edit("~2024-1", 1000, 1000), None);
+
+        assert_eq!(assessment.reasons, vec!["anonymous"]);
+    }
+
+    #[test]
+    fn recently_registered_accounts_are_new() {
+        let scorer = scorer(&[]);
+
+        scorer.note_registration(&json::object! {
+            "type" => "log",
+            "log_type" => "newusers",
+            "user" => "Bob",
+            "title" => "User:Bob",
+            "timestamp" => 900,
+        });
+
+        assert_eq!(scorer.assess(&edit("Bob", 1000, 1000), None).reasons, vec!["new account"]);
+        assert!(scorer.assess(&edit("Carol", 1000, 1000), None).reasons.is_empty());
+    }
+
+    #[test]
+    fn added_links_and_blacklisted_words_are_scored() {
+        let diff_lines = vec![
+            DiffLine::Removed("plain text".to_string()),
+            DiffLine::Added("buy cheap pills at https://spam.example".to_string()),
+        ];
+
+        let assessment = scorer(&["cheap pills"]).assess(&edit("Alice", 1000, 1100), Some(&diff_lines));
+
+        assert_eq!(assessment.score, 5);
+        assert_eq!(assessment.reasons, vec!["external links", "blacklisted words"]);
+    }
+
+    #[test]
+    fn moved_links_are_not_scored() {
+        let diff_lines = vec![
+            DiffLine::Removed("see https://example.org".to_string()),
+            DiffLine::Added("See https://example.org".to_string()),
+        ];
+
+        let assessment = scorer(&[]).assess(&edit("Alice", 1000, 1000), Some(&diff_lines));
+
+        assert!(assessment.reasons.is_empty());
+    }
+}
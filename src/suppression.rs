@@ -0,0 +1,100 @@
+/*
+ * MEDIAWIKI EVENT SUPPRESSION
+ */
+
+use std::sync::Arc;
+
+use regex::Regex;
+
+use crate::config::SuppressionConfig;
+use crate::control::Control;
+
+pub struct Suppressor {
+    config: SuppressionConfig,
+    title_rgxs: Vec<Regex>,
+    // keeps the number of events matched per rule
+    control: Arc<Control>,
+}
+
+impl Suppressor {
+    pub fn new(config: &SuppressionConfig, control: Arc<Control>) -> Suppressor {
+        let title_rgxs = config.ignore_titles
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern)
+                    .unwrap_or_else(|err| panic!("✘ Invalid title pattern {:?}: {}", pattern, err))
+            })
+            .collect();
+
+        Suppressor {
+            config: config.clone(),
+            title_rgxs,
+            control,
+        }
+    }
+
+    fn count(&self, rule: String, evt: &json::JsonValue) {
+        let counter = self.control.record_suppression(&rule);
+
+        println!(
+            "Suppression rule '{}' matched {} event on '{}' ({} so far)",
+            rule, evt["type"], evt["title"], counter
+        );
+    }
+
+    fn drop_rule(&self, evt: &json::JsonValue) -> Option<String> {
+        if self.config.ignore_bots && evt["bot"] == true {
+            return Some("bot".to_string());
+        }
+
+        if let Some(user) = evt["user"].as_str() {
+            if self.config.ignore_users.iter().any(|ignored| ignored == user) {
+                return Some(format!("user:{}", user));
+            }
+        }
+
+        if let Some(title) = evt["title"].as_str() {
+            if let Some(rgx) = self.title_rgxs.iter().find(|rgx| rgx.is_match(title)) {
+                return Some(format!("title:{}", rgx.as_str()));
+            }
+        }
+
+        None
+    }
+
+    fn silent_rule(&self, evt: &json::JsonValue) -> Option<String> {
+        if self.config.silent_minor && evt["minor"] == true {
+            return Some("minor".to_string());
+        }
+
+        if self.config.silent_patrolled && evt["patrolled"] == true {
+            return Some("patrolled".to_string());
+        }
+
+        None
+    }
+
+    // Events matching an ignore rule aren't reported at all.
+    pub fn should_drop(&self, evt: &json::JsonValue) -> bool {
+        match self.drop_rule(evt) {
+            Some(rule) => {
+                self.count(rule, evt);
+
+                true
+            }
+            None => false
+        }
+    }
+
+    // Events matching a demotion rule are posted without notification.
+    pub fn should_silence(&self, evt: &json::JsonValue) -> bool {
+        match self.silent_rule(evt) {
+            Some(rule) => {
+                self.count(rule, evt);
+
+                true
+            }
+            None => false
+        }
+    }
+}
@@ -0,0 +1,23 @@
+/*
+ * MESSAGE THREADS
+ *
+ * Remembers the message posted about an entity (a pull-request, an
+ * issue, a wiki revision) so later events on it can reply to or edit
+ * that message instead of starting a new one.
+ */
+
+use telegram_bot::InlineKeyboardMarkup;
+
+use crate::lru::Lru;
+
+#[derive(Clone)]
+pub struct Thread {
+    pub chat_id: i64,
+    pub message_id: i64,
+    // as posted or last edited, for appending to it
+    pub text: String,
+    pub reply_markup: Option<InlineKeyboardMarkup>,
+}
+
+// by entity key
+pub type MessageThreads = Lru<String, Thread>;
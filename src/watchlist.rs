@@ -0,0 +1,203 @@
+/*
+ * PAGE WATCHLISTS
+ *
+ * Watchers come from the configuration and from the `/watch` bot
+ * command. The latter are written to disk on every change so they
+ * survive restarts.
+ */
+
+use std::{
+    fs,
+    sync::Mutex,
+};
+
+use crate::config::{WatchlistConfig, Watcher};
+use crate::{htmlescape_str, save_state};
+use crate::wiki_api::normalize_title;
+
+pub struct Watchlist {
+    watchers: Vec<Watcher>,
+    state_path: String,
+    // subscribed through bot commands
+    subscribed: Mutex<Vec<Watcher>>,
+}
+
+fn strip_category_prefix(category: &str) -> String {
+    let category = normalize_title(category);
+
+    match category.find(':') {
+        Some(idx) if category[..idx].eq_ignore_ascii_case("Category") => category[idx + 1..].to_string(),
+        _ => category
+    }
+}
+
+fn is_category(title: &str) -> bool {
+    strip_category_prefix(title) != normalize_title(title)
+}
+
+impl Watcher {
+    // Telegram HTML mention, preferring the public @username
+    pub fn mention(&self) -> String {
+        match self.username {
+            Some(ref username) => format!("@{}", username),
+            None => format!(
+                r#"<a href="tg://user?id={}">{}</a>"#,
+                self.telegram_user_id,
+                htmlescape_str(self.name.clone().unwrap_or_else(|| self.telegram_user_id.to_string()))
+            )
+        }
+    }
+
+    fn watches_page(&self, title: &str) -> bool {
+        let title = normalize_title(title);
+
+        self.pages.iter().any(|page| normalize_title(page) == title)
+    }
+
+    fn watches_category(&self, categories: &[String]) -> bool {
+        self.categories.iter().any(|watched| {
+            let watched = strip_category_prefix(watched);
+
+            categories.iter().any(|category| strip_category_prefix(category) == watched)
+        })
+    }
+
+    // Watched pages and categories, the latter with their prefix
+    fn watched(&self) -> Vec<String> {
+        self.pages
+            .iter()
+            .cloned()
+            .chain(self.categories.iter().map(|category| format!("Category:{}", strip_category_prefix(category))))
+            .collect()
+    }
+}
+
+impl Watchlist {
+    pub fn new(config: &WatchlistConfig) -> Watchlist {
+        let subscribed = match fs::read_to_string(&config.state_path) {
+            Ok(raw) => serde_json::from_str(&raw).unwrap_or_else(|err| {
+                println!("couldn't parse watchlist state {}: {}", config.state_path, err);
+
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+
+        Watchlist {
+            watchers: config.watchers.clone(),
+            state_path: config.state_path.clone(),
+            subscribed: Mutex::new(subscribed),
+        }
+    }
+
+    fn save(&self, subscribed: &[Watcher]) {
+        if let Err(err) = save_state(&self.state_path, subscribed) {
+            println!("couldn't save watchlist state {}: {}", self.state_path, err);
+        }
+    }
+
+    // Whether resolving page categories is needed at all
+    pub fn has_category_watches(&self) -> bool {
+        self.watchers.iter().any(|watcher| !watcher.categories.is_empty())
+            || self.subscribed.lock().unwrap().iter().any(|watcher| !watcher.categories.is_empty())
+    }
+
+    pub fn watchers_of(&self, titles: &[String], categories: &[String]) -> Vec<Watcher> {
+        let subscribed = self.subscribed.lock().unwrap();
+
+        self.watchers
+            .iter()
+            .chain(subscribed.iter())
+            .filter(|watcher| {
+                titles.iter().any(|title| watcher.watches_page(title))
+                    || watcher.watches_category(categories)
+            })
+            .cloned()
+            .collect()
+    }
+
+    // Subscribe a user to a page or "Category:…"; returns false if it
+    // was watched already. `watcher` carries the user's details.
+    pub fn watch(&self, watcher: Watcher, title: &str) -> bool {
+        let mut subscribed = self.subscribed.lock().unwrap();
+
+        let idx = match subscribed.iter().position(|known| known.telegram_user_id == watcher.telegram_user_id) {
+            Some(idx) => idx,
+            None => {
+                subscribed.push(watcher.clone());
+
+                subscribed.len() - 1
+            }
+        };
+
+        let entry = &mut subscribed[idx];
+
+        // keep the details current, e.g. after a change of username
+        entry.username = watcher.username;
+        entry.name = watcher.name;
+        entry.dm = watcher.dm;
+
+        let is_new = match is_category(title) {
+            true if !entry.watches_category(&[title.to_string()]) => {
+                entry.categories.push(strip_category_prefix(title));
+
+                true
+            }
+            false if !entry.watches_page(title) => {
+                entry.pages.push(normalize_title(title));
+
+                true
+            }
+            _ => false,
+        };
+
+        self.save(&subscribed);
+
+        is_new
+    }
+
+    // Returns false if the user wasn't subscribed to the page or category
+    pub fn unwatch(&self, telegram_user_id: i64, title: &str) -> bool {
+        let mut subscribed = self.subscribed.lock().unwrap();
+
+        let entry = match subscribed.iter_mut().find(|known| known.telegram_user_id == telegram_user_id) {
+            Some(entry) => entry,
+            None => return false,
+        };
+
+        let (pages, categories) = (entry.pages.len(), entry.categories.len());
+
+        match is_category(title) {
+            true => {
+                let category = strip_category_prefix(title);
+
+                entry.categories.retain(|watched| strip_category_prefix(watched) != category);
+            }
+            false => {
+                let page = normalize_title(title);
+
+                entry.pages.retain(|watched| normalize_title(watched) != page);
+            }
+        }
+
+        let is_removed = entry.pages.len() != pages || entry.categories.len() != categories;
+
+        subscribed.retain(|known| !known.pages.is_empty() || !known.categories.is_empty());
+
+        self.save(&subscribed);
+
+        is_removed
+    }
+
+    // Pages and categories watched by a user, configured or subscribed
+    pub fn watched_by(&self, telegram_user_id: i64) -> Vec<String> {
+        let subscribed = self.subscribed.lock().unwrap();
+
+        self.watchers
+            .iter()
+            .chain(subscribed.iter())
+            .filter(|watcher| watcher.telegram_user_id == telegram_user_id)
+            .flat_map(|watcher| watcher.watched())
+            .collect()
+    }
+}
@@ -0,0 +1,630 @@
+/*
+ * MEDIAWIKI API CLIENT
+ */
+
+use std::{
+    collections::HashMap,
+    io::Read,
+    sync::Mutex,
+    time::Duration,
+};
+
+use hyper::Client;
+use hyper::client::Response;
+use hyper::header::{ContentType, Cookie, SetCookie};
+use hyper::net::HttpsConnector;
+use hyper_native_tls::NativeTlsClient;
+use regex::Regex;
+use url::form_urlencoded;
+
+use crate::config::{MediaWikiConfig, WikiConfig};
+use crate::lru::Lru;
+
+// the API accepts at most 50 ids per request for regular accounts
+const MAX_REVIDS_PER_QUERY: usize = 50;
+
+// MediaWiki treats spaces and underscores in titles as equivalent,
+// the canonical form in urls uses underscores.
+pub fn normalize_title(title: &str) -> String {
+    title.trim().replace(' ', "_")
+}
+
+// Serialize query parameters, normalizing any `title`/`titles` value.
+pub fn encode_query(params: &[(&str, &str)]) -> String {
+    let mut serializer = form_urlencoded::Serializer::new(String::new());
+
+    for (key, value) in params {
+        match *key {
+            "title" | "titles" => serializer.append_pair(key, &normalize_title(value)),
+            _ => serializer.append_pair(key, value),
+        };
+    }
+
+    serializer.finish()
+}
+
+// Encode a title for use as the path of an article url. Namespace
+// separators and subpage slashes are kept readable.
+pub fn encode_title_path(title: &str) -> String {
+    form_urlencoded::byte_serialize(normalize_title(title).as_bytes())
+        .collect::<String>()
+        .replace("%3A", ":")
+        .replace("%2F", "/")
+}
+
+// (user, comment, parentid, page title)
+#[derive(Debug, Clone)]
+pub struct RevInfo(pub String, pub String, pub String, pub String);
+
+// Metadata of the latest version of a file
+pub struct ImageInfo {
+    pub size: u64,
+    pub width: u64,
+    pub height: u64,
+    pub mime: String,
+    pub comment: String,
+    pub url: String,
+    // scaled rendering, also for formats Telegram can't display
+    pub thumb_url: Option<String>,
+}
+
+// A changed line of a diff, as plain text
+pub enum DiffLine {
+    Added(String),
+    Removed(String),
+}
+
+// Moderation actions that can be taken on a revision
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WikiAction {
+    Patrol,
+    Rollback,
+    Approve,
+}
+
+// Logged-in session of a wiki account, authenticated with a bot password
+pub struct WikiSession {
+    username: String,
+    password: String,
+    cookies: HashMap<String, String>,
+    tokens: Option<json::JsonValue>,
+}
+
+impl WikiSession {
+    pub fn new(username: &str, password: &str) -> WikiSession {
+        WikiSession {
+            username: username.to_string(),
+            password: password.to_string(),
+            cookies: HashMap::new(),
+            tokens: None,
+        }
+    }
+
+    fn cookie_header(&self) -> Cookie {
+        Cookie(
+            self.cookies
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect()
+        )
+    }
+
+    fn store_cookies(&mut self, res: &Response) {
+        let set_cookies = match res.headers.get::<SetCookie>() {
+            Some(set_cookies) => set_cookies,
+            None => return,
+        };
+
+        for set_cookie in set_cookies.iter() {
+            let pair = set_cookie.split(';').next().unwrap_or("");
+
+            if let Some(idx) = pair.find('=') {
+                self.cookies.insert(pair[..idx].trim().to_string(), pair[idx + 1..].trim().to_string());
+            }
+        }
+    }
+}
+
+// Error message of an API response, if any
+fn api_error(data: &json::JsonValue) -> Option<String> {
+    match data["error"].is_object() {
+        true => Some(format!("{}: {}", data["error"]["code"], data["error"]["info"])),
+        false => None,
+    }
+}
+
+pub struct WikiApiClient {
+    client: Client,
+    api_url: String,
+    // revisions are immutable once saved, so entries never go stale
+    revision_cache: Mutex<Lru<u32, RevInfo>>,
+    diff_line_rgx: Regex,
+    html_tag_rgx: Regex,
+}
+
+impl WikiApiClient {
+    pub fn new(config: &MediaWikiConfig, wiki: &WikiConfig) -> WikiApiClient {
+        let ssl = NativeTlsClient::new().unwrap();
+        let connector = HttpsConnector::new(ssl);
+
+        let mut client = Client::with_connector(connector);
+
+        let timeout = Duration::from_secs(config.api_timeout_secs);
+
+        client.set_read_timeout(Some(timeout));
+        client.set_write_timeout(Some(timeout));
+
+        WikiApiClient {
+            client,
+            api_url: wiki.api_url.clone(),
+            revision_cache: Mutex::new(Lru::new(config.revision_cache_size)),
+            diff_line_rgx: Regex::new(
+                r#"(?s)<td class="diff-(added|deleted)line[^"]*"[^>]*>(.*?)</td>"#
+            ).unwrap(),
+            html_tag_rgx: Regex::new(r"<[^>]*>").unwrap(),
+        }
+    }
+
+    fn load_json(&self, params: &[(&str, &str)]) -> Option<json::JsonValue> {
+        let url = format!("{}?{}", self.api_url, encode_query(params));
+
+        let res = match self.client.get(&url).send() {
+            Ok(res) => res,
+            Err(err) => {
+                eprintln!("MediaWiki API request failed: {:?}", err);
+
+                return None;
+            }
+        };
+
+        WikiApiClient::read_json(res)
+    }
+
+    fn read_json(mut res: Response) -> Option<json::JsonValue> {
+        let mut buf = String::new();
+
+        if res.read_to_string(&mut buf).is_err() {
+            return None;
+        }
+
+        json::parse(&buf).ok()
+    }
+
+    // Request on behalf of a session, form-encoded as POST body if
+    // `post` is set (required for anything that changes the wiki).
+    fn session_request(&self, session: &mut WikiSession, params: &[(&str, &str)], post: bool) -> Result<json::JsonValue, String> {
+        let query = encode_query(params);
+
+        let req = match post {
+            true => self.client
+                .post(&self.api_url)
+                .header(ContentType::form_url_encoded())
+                .body(query.as_str()),
+            false => self.client.get(&format!("{}?{}", self.api_url, query)),
+        };
+
+        let res = req
+            .header(session.cookie_header())
+            .send()
+            .map_err(|err| format!("request failed: {}", err))?;
+
+        session.store_cookies(&res);
+
+        let data = WikiApiClient::read_json(res)
+            .ok_or_else(|| "invalid response".to_string())?;
+
+        match api_error(&data) {
+            Some(err) => Err(err),
+            None => Ok(data),
+        }
+    }
+
+    fn login(&self, session: &mut WikiSession) -> Result<(), String> {
+        session.cookies.clear();
+        session.tokens = None;
+
+        let token_data = self.session_request(session, &[
+            ("action", "query"),
+            ("meta", "tokens"),
+            ("type", "login"),
+            ("format", "json"),
+        ], false)?;
+
+        let login_token = token_data["query"]["tokens"]["logintoken"].to_string();
+        let username = session.username.clone();
+        let password = session.password.clone();
+
+        let login_data = self.session_request(session, &[
+            ("action", "login"),
+            ("lgname", &username),
+            ("lgpassword", &password),
+            ("lgtoken", &login_token),
+            ("format", "json"),
+        ], true)?;
+
+        if login_data["login"]["result"] != "Success" {
+            return Err(format!(
+                "login as {} failed: {}",
+                username,
+                login_data["login"]["reason"]
+            ));
+        }
+
+        let token_data = self.session_request(session, &[
+            ("action", "query"),
+            ("meta", "tokens"),
+            ("type", "csrf|patrol|rollback"),
+            ("format", "json"),
+        ], false)?;
+
+        session.tokens = Some(token_data["query"]["tokens"].clone());
+
+        Ok(())
+    }
+
+    fn try_perform(&self, session: &mut WikiSession, action: WikiAction, rev_id: u32) -> Result<(), String> {
+        if session.tokens.is_none() {
+            self.login(session)?;
+        }
+
+        let tokens = session.tokens.clone().unwrap_or_else(json::JsonValue::new_object);
+        let rev_id_str = rev_id.to_string();
+
+        match action {
+            WikiAction::Patrol => self.session_request(session, &[
+                ("action", "patrol"),
+                ("revid", &rev_id_str),
+                ("token", &tokens["patroltoken"].to_string()),
+                ("assert", "user"),
+                ("format", "json"),
+            ], true).map(|_| ()),
+            WikiAction::Rollback => {
+                let rev_info = self.get_revision_info(rev_id)
+                    .ok_or_else(|| format!("revision {} not found", rev_id))?;
+
+                // rollback reverts whoever edited the page last, which
+                // may no longer be the author of this revision
+                if self.get_latest_rev_id(&rev_info.3) != Some(rev_id) {
+                    return Err(format!("revision {} is no longer the latest of {}", rev_id, rev_info.3));
+                }
+
+                self.session_request(session, &[
+                    ("action", "rollback"),
+                    ("title", &rev_info.3),
+                    ("user", &rev_info.0),
+                    ("token", &tokens["rollbacktoken"].to_string()),
+                    ("assert", "user"),
+                    ("format", "json"),
+                ], true).map(|_| ())
+            }
+            // provided by the Approved Revs extension
+            WikiAction::Approve => self.session_request(session, &[
+                ("action", "approve"),
+                ("revid", &rev_id_str),
+                ("token", &tokens["csrftoken"].to_string()),
+                ("assert", "user"),
+                ("format", "json"),
+            ], true).map(|_| ()),
+        }
+    }
+
+    // Take a moderation action as the session's account, logging in
+    // again once if the session expired in the meantime.
+    pub fn perform(&self, session: &mut WikiSession, action: WikiAction, rev_id: u32) -> Result<(), String> {
+        match self.try_perform(session, action, rev_id) {
+            Err(ref err) if err.starts_with("badtoken") || err.starts_with("assertuserfailed") => {
+                session.tokens = None;
+
+                self.try_perform(session, action, rev_id)
+            }
+            result => result,
+        }
+    }
+
+    // Metadata of all given revisions; those that aren't cached yet are
+    // fetched, coalesced into as few `action=query` requests as possible.
+    // Revisions that couldn't be found are missing from the result.
+    pub fn prefetch_revisions(&self, rev_ids: &[u32]) -> HashMap<u32, RevInfo> {
+        let mut revisions = HashMap::new();
+        let mut missing = Vec::new();
+
+        {
+            let mut cache = self.revision_cache.lock().unwrap();
+
+            for rev_id in rev_ids {
+                match cache.get(rev_id) {
+                    Some(rev_info) => {
+                        revisions.insert(*rev_id, rev_info);
+                    }
+                    None => missing.push(*rev_id),
+                }
+            }
+        }
+
+        missing.sort_unstable();
+        missing.dedup();
+
+        for chunk in missing.chunks(MAX_REVIDS_PER_QUERY) {
+            let revids = chunk
+                .iter()
+                .map(|rev_id| rev_id.to_string())
+                .collect::<Vec<String>>()
+                .join("|");
+
+            let revision_data = match self.load_json(&[
+                ("action", "query"),
+                ("prop", "revisions"),
+                ("revids", &revids),
+                ("rvprop", "timestamp|user|comment|ids"),
+                ("format", "json"),
+            ]) {
+                Some(revision_data) => revision_data,
+                None => continue,
+            };
+
+            let mut cache = self.revision_cache.lock().unwrap();
+
+            for (_, page) in revision_data["query"]["pages"].entries() {
+                for revision in page["revisions"].members() {
+                    let rev_id = match revision["revid"].as_u32() {
+                        Some(rev_id) => rev_id,
+                        None => continue,
+                    };
+
+                    let rev_info = RevInfo(
+                        revision["user"].to_string(),
+                        revision["comment"].to_string(),
+                        revision["parentid"].to_string(),
+                        page["title"].to_string(),
+                    );
+
+                    cache.insert(rev_id, rev_info.clone());
+                    revisions.insert(rev_id, rev_info);
+                }
+            }
+        }
+
+        revisions
+    }
+
+    pub fn get_revision_info(&self, rev_id: u32) -> Option<RevInfo> {
+        self.prefetch_revisions(&[rev_id]).remove(&rev_id)
+    }
+
+    // Id of the current revision of a page
+    pub fn get_latest_rev_id(&self, title: &str) -> Option<u32> {
+        let revision_data = self.load_json(&[
+            ("action", "query"),
+            ("prop", "revisions"),
+            ("titles", title),
+            ("rvprop", "ids"),
+            ("format", "json"),
+        ])?;
+
+        let (_, page) = revision_data["query"]["pages"].entries().next()?;
+
+        page["revisions"][0]["revid"].as_u32()
+    }
+
+    // Wikitext of a revision
+    pub fn get_revision_content(&self, rev_id: u32) -> Option<String> {
+        let revision_data = self.load_json(&[
+            ("action", "query"),
+            ("prop", "revisions"),
+            ("revids", &rev_id.to_string()),
+            ("rvprop", "content"),
+            ("rvslots", "main"),
+            ("format", "json"),
+        ])?;
+
+        let (_, page) = revision_data["query"]["pages"].entries().next()?;
+
+        let slot = &page["revisions"][0]["slots"]["main"];
+
+        // "*" in the default format, "content" in formatversion 2
+        slot["*"].as_str().or_else(|| slot["content"].as_str()).map(|content| content.to_string())
+    }
+
+    // Changed lines between two revisions, in diff order. Context
+    // lines and moved paragraphs are left out.
+    pub fn get_diff_lines(&self, from_rev: u32, to_rev: u32) -> Option<Vec<DiffLine>> {
+        let compare_data = self.load_json(&[
+            ("action", "compare"),
+            ("fromrev", &from_rev.to_string()),
+            ("torev", &to_rev.to_string()),
+            ("prop", "diff"),
+            ("format", "json"),
+        ])?;
+
+        let diff_html = compare_data["compare"]["*"].as_str()?;
+
+        let lines = self.diff_line_rgx
+            .captures_iter(diff_html)
+            .filter_map(|caps| {
+                let text = self.html_tag_rgx.replace_all(&caps[2], "");
+                let text = htmlescape::decode_html(&text).unwrap_or_else(|_| text.to_string());
+                let text = text.trim().to_string();
+
+                if text.is_empty() {
+                    return None;
+                }
+
+                match &caps[1] {
+                    "added" => Some(DiffLine::Added(text)),
+                    _ => Some(DiffLine::Removed(text))
+                }
+            })
+            .collect::<Vec<DiffLine>>();
+
+        Some(lines)
+    }
+
+    // Categories of a page, including the "Category:" prefix
+    pub fn get_page_categories(&self, title: &str) -> Vec<String> {
+        let category_data = match self.load_json(&[
+            ("action", "query"),
+            ("prop", "categories"),
+            ("titles", title),
+            ("cllimit", "max"),
+            ("format", "json"),
+        ]) {
+            Some(category_data) => category_data,
+            None => return Vec::new(),
+        };
+
+        category_data["query"]["pages"]
+            .entries()
+            .flat_map(|(_, page)| page["categories"].members())
+            .filter_map(|category| category["title"].as_str())
+            .map(|category| category.to_string())
+            .collect()
+    }
+
+    // File metadata, with a thumbnail of the given width if any
+    pub fn get_image_info(&self, title: &str, thumb_width: Option<u32>) -> Option<ImageInfo> {
+        let thumb_width = thumb_width.map(|thumb_width| thumb_width.to_string());
+
+        let mut params = vec![
+            ("action", "query"),
+            ("prop", "imageinfo"),
+            ("titles", title),
+            ("iiprop", "size|mime|comment|url"),
+            ("format", "json"),
+        ];
+
+        if let Some(ref thumb_width) = thumb_width {
+            params.push(("iiurlwidth", thumb_width));
+        }
+
+        let image_data = self.load_json(&params)?;
+
+        let (_, page) = image_data["query"]["pages"].entries().next()?;
+
+        let info = &page["imageinfo"][0];
+
+        if info.is_null() {
+            return None;
+        }
+
+        Some(ImageInfo {
+            size: info["size"].as_u64().unwrap_or(0),
+            width: info["width"].as_u64().unwrap_or(0),
+            height: info["height"].as_u64().unwrap_or(0),
+            mime: info["mime"].as_str().unwrap_or("").to_string(),
+            comment: info["comment"].as_str().unwrap_or("").to_string(),
+            url: info["url"].as_str().unwrap_or("").to_string(),
+            thumb_url: info["thumburl"].as_str().map(|thumb_url| thumb_url.to_string()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        io::Read,
+        sync::{Arc, Mutex},
+    };
+
+    use hyper::server::{Request, Response, Server};
+    use hyper::uri::RequestUri;
+    use url::form_urlencoded;
+
+    use super::*;
+
+    // Wiki API answering just enough for logging in and moderating
+    // revision 100 of "Page"; records the actions posted to it.
+    fn mock_wiki(latest_rev_id: u32) -> (WikiApiClient, Arc<Mutex<Vec<String>>>) {
+        let posted = Arc::new(Mutex::new(Vec::new()));
+        let recorder = posted.clone();
+
+        let server = Server::http("127.0.0.1:0").unwrap().handle(move |mut req: Request, res: Response| {
+            let mut query = match req.uri {
+                RequestUri::AbsolutePath(ref path) => path.split('?').nth(1).unwrap_or("").to_string(),
+                _ => String::new(),
+            };
+
+            if req.method == hyper::method::Method::Post {
+                req.read_to_string(&mut query).unwrap();
+
+                let action = form_urlencoded::parse(query.as_bytes())
+                    .find(|(key, _)| key == "action")
+                    .map(|(_, value)| value.into_owned())
+                    .unwrap_or_default();
+
+                recorder.lock().unwrap().push(action);
+            }
+
+            let params = form_urlencoded::parse(query.as_bytes())
+                .into_owned()
+                .collect::<HashMap<String, String>>();
+
+            let param = |name: &str| params.get(name).map(|value| value.as_str()).unwrap_or("");
+
+            let body = match (param("action"), param("type"), param("prop")) {
+                ("query", "login", _) => r#"{"query":{"tokens":{"logintoken":"login+\\"}}}"#.to_string(),
+                ("query", _, "") => r#"{"query":{"tokens":{"csrftoken":"csrf+\\","patroltoken":"patrol+\\","rollbacktoken":"rollback+\\"}}}"#.to_string(),
+                ("query", _, _) if !param("revids").is_empty() => format!(
+                    r#"{{"query":{{"pages":{{"1":{{"title":"Page","revisions":[{{"revid":{},"parentid":99,"user":"Vandal","comment":""}}]}}}}}}}}"#,
+                    param("revids")
+                ),
+                ("query", _, _) => format!(
+                    r#"{{"query":{{"pages":{{"1":{{"title":"Page","revisions":[{{"revid":{}}}]}}}}}}}}"#,
+                    latest_rev_id
+                ),
+                ("login", _, _) if param("lgpassword") == "secret" => r#"{"login":{"result":"Success"}}"#.to_string(),
+                ("login", _, _) => r#"{"login":{"result":"Failed","reason":"wrong password"}}"#.to_string(),
+                (action, _, _) => format!(r#"{{"{}":{{}}}}"#, action),
+            };
+
+            res.send(body.as_bytes()).unwrap();
+        }).unwrap();
+
+        let wiki = WikiConfig {
+            api_url: format!("http://{}/api.php", server.socket),
+            ..WikiConfig::default()
+        };
+
+        // the server is left running until the tests exit
+        std::mem::forget(server);
+
+        (WikiApiClient::new(&MediaWikiConfig::default(), &wiki), posted)
+    }
+
+    #[test]
+    fn patrols_after_logging_in() {
+        let (wiki_api, posted) = mock_wiki(100);
+        let mut session = WikiSession::new("Moderator@eop", "secret");
+
+        assert_eq!(wiki_api.perform(&mut session, WikiAction::Patrol, 100), Ok(()));
+        assert_eq!(*posted.lock().unwrap(), vec!["login", "patrol"]);
+
+        // the session is reused
+        assert_eq!(wiki_api.perform(&mut session, WikiAction::Approve, 100), Ok(()));
+        assert_eq!(*posted.lock().unwrap(), vec!["login", "patrol", "approve"]);
+    }
+
+    #[test]
+    fn fails_on_wrong_password() {
+        let (wiki_api, posted) = mock_wiki(100);
+        let mut session = WikiSession::new("Moderator@eop", "wrong");
+
+        assert!(wiki_api.perform(&mut session, WikiAction::Patrol, 100).is_err());
+        assert_eq!(*posted.lock().unwrap(), vec!["login"]);
+    }
+
+    #[test]
+    fn rolls_back_latest_revision_only() {
+        let (wiki_api, posted) = mock_wiki(100);
+        let mut session = WikiSession::new("Moderator@eop", "secret");
+
+        assert_eq!(wiki_api.perform(&mut session, WikiAction::Rollback, 100), Ok(()));
+        assert_eq!(*posted.lock().unwrap(), vec!["login", "rollback"]);
+
+        let (wiki_api, posted) = mock_wiki(101);
+        let mut session = WikiSession::new("Moderator@eop", "secret");
+
+        assert!(wiki_api.perform(&mut session, WikiAction::Rollback, 100).is_err());
+        assert_eq!(*posted.lock().unwrap(), vec!["login"]);
+    }
+}
@@ -0,0 +1,188 @@
+/*
+ * WIKITEXT IN SUMMARIES
+ *
+ * Renders the little wikitext found in edit summaries and log comments
+ * as Telegram HTML: section autocomments and internal links become
+ * links, external links keep their label, templates are elided.
+ */
+
+use regex::{Captures, Regex};
+
+use crate::htmlescape_str;
+use crate::wiki_api::encode_title_path;
+
+pub struct WikitextRenderer {
+    article_url: String,
+    markup_rgx: Regex,
+    template_rgx: Regex,
+}
+
+impl WikitextRenderer {
+    pub fn new(article_url: &str) -> WikitextRenderer {
+        WikitextRenderer {
+            article_url: article_url.to_string(),
+            markup_rgx: Regex::new(concat!(
+                r"/\*\s*(?P<section>.*?)\s*\*/",
+                r"|\[\[(?P<target>[^\[\]|]*)(?:\|(?P<label>[^\[\]]*))?\]\]",
+                r"|\[(?P<url>https?://[^\s\[\]]+)(?:\s+(?P<url_label>[^\[\]]*))?\]",
+                r"|(?P<quotes>'{2,})",
+            )).unwrap(),
+            // innermost templates, nested ones are removed from the inside out
+            template_rgx: Regex::new(r"\{\{[^{}]*\}\}").unwrap(),
+        }
+    }
+
+    // "Page#Some section" -> article url with anchor
+    fn get_url(&self, target: &str, page: &str) -> String {
+        let (title, anchor) = match target.find('#') {
+            Some(idx) => (&target[..idx], Some(&target[idx + 1..])),
+            None => (target, None),
+        };
+
+        // "[[#Section]]" refers to the page itself
+        let title = match title.is_empty() {
+            true => page,
+            false => title,
+        };
+
+        match anchor {
+            Some(anchor) => format!(
+                "{}/{}#{}",
+                self.article_url,
+                encode_title_path(title),
+                encode_title_path(anchor.trim())
+            ),
+            None => format!("{}/{}", self.article_url, encode_title_path(title)),
+        }
+    }
+
+    fn render_markup(&self, caps: &Captures, page: &str, has_text_after: bool) -> String {
+        if let Some(section) = caps.name("section") {
+            return format!(
+                r#"→<a href="{}">{}</a>{}"#,
+                self.get_url(&format!("#{}", section.as_str()), page),
+                htmlescape_str(section.as_str()),
+                match has_text_after {
+                    true => ":",
+                    false => "",
+                }
+            );
+        }
+
+        if let Some(target) = caps.name("target") {
+            let target = target.as_str().trim().trim_start_matches(':');
+
+            let label = match caps.name("label").map(|label| label.as_str().trim()) {
+                Some(label) if !label.is_empty() => label,
+                _ => target,
+            };
+
+            return format!(
+                r#"<a href="{}">{}</a>"#,
+                self.get_url(target, page),
+                htmlescape_str(label)
+            );
+        }
+
+        if let Some(url) = caps.name("url") {
+            let label = match caps.name("url_label").map(|label| label.as_str().trim()) {
+                Some(label) if !label.is_empty() => label,
+                _ => url.as_str(),
+            };
+
+            return format!(
+                r#"<a href="{}">{}</a>"#,
+                htmlescape_str(url.as_str()),
+                htmlescape_str(label)
+            );
+        }
+
+        // bold and italic quotes are dropped
+        String::new()
+    }
+
+    // Wikitext of a summary on the given page, as Telegram HTML
+    pub fn render(&self, wikitext: &str, page: &str) -> String {
+        let mut text = wikitext.to_string();
+
+        while self.template_rgx.is_match(&text) {
+            text = self.template_rgx.replace_all(&text, "…").into_owned();
+        }
+
+        let mut html = String::with_capacity(text.len());
+        let mut last = 0;
+
+        for caps in self.markup_rgx.captures_iter(&text) {
+            let markup = caps.get(0).unwrap();
+
+            html.push_str(&htmlescape_str(&text[last..markup.start()]));
+
+            let has_text_after = !text[markup.end()..].trim().is_empty();
+
+            html.push_str(&self.render_markup(&caps, page, has_text_after));
+
+            last = markup.end();
+        }
+
+        html.push_str(&htmlescape_str(&text[last..]));
+
+        html
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(wikitext: &str) -> String {
+        WikitextRenderer::new("https://wiki.example/wiki").render(wikitext, "Some page")
+    }
+
+    #[test]
+    fn leaves_plain_text_escaped() {
+        assert_eq!(render("fixed a < b & c"), "fixed a &lt; b &amp; c");
+    }
+
+    #[test]
+    fn links_section_autocomments() {
+        assert_eq!(
+            render("/* Dosage */ fixed units"),
+            r#"→<a href="https://wiki.example/wiki/Some_page#Dosage">Dosage</a>: fixed units"#
+        );
+        assert_eq!(
+            render("/* Side effects */"),
+            r#"→<a href="https://wiki.example/wiki/Some_page#Side_effects">Side effects</a>"#
+        );
+    }
+
+    #[test]
+    fn links_internal_links() {
+        assert_eq!(
+            render("see [[Other page]] and [[Other page#History|its history]]"),
+            concat!(
+                r#"see <a href="https://wiki.example/wiki/Other_page">Other page</a>"#,
+                r#" and <a href="https://wiki.example/wiki/Other_page#History">its history</a>"#
+            )
+        );
+        assert_eq!(
+            render("[[:Category:Stimulants]]"),
+            r#"<a href="https://wiki.example/wiki/Category:Stimulants">Category:Stimulants</a>"#
+        );
+    }
+
+    #[test]
+    fn keeps_labels_of_external_links() {
+        assert_eq!(
+            render("per [https://example.org/a?b=1&c=2 this study] and [https://example.org]"),
+            concat!(
+                r#"per <a href="https://example.org/a?b=1&amp;c=2">this study</a>"#,
+                r#" and <a href="https://example.org">https://example.org</a>"#
+            )
+        );
+    }
+
+    #[test]
+    fn elides_templates_and_quotes() {
+        assert_eq!(render("added {{cite|{{nested}}}} to '''bold''' text"), "added … to bold text");
+    }
+}